@@ -0,0 +1,106 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use nightmare::prelude::*;
+
+const HANDLES: usize = 10_000;
+
+/// Fills both storages with a value for every `stride`th handle
+fn populate(stride: usize) -> (GenerationalVec<u64>, SparseSet<u64>) {
+    let mut allocator = HandleAllocator::new();
+    let mut generational_vec = GenerationalVec::new(SlotVec::default());
+    let mut sparse_set = SparseSet::new();
+    for index in 0..HANDLES {
+        let handle = allocator.allocate();
+        if index % stride == 0 {
+            generational_vec.insert(handle, index as u64).unwrap();
+            sparse_set.insert(handle, index as u64).unwrap();
+        }
+    }
+    (generational_vec, sparse_set)
+}
+
+fn iteration(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iterate");
+    for stride in [1, 10, 100] {
+        let (generational_vec, sparse_set) = populate(stride);
+        group.bench_with_input(
+            BenchmarkId::new("GenerationalVec", stride),
+            &generational_vec,
+            |b, storage| b.iter(|| storage.iter().map(|(_, value)| *value).sum::<u64>()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("SparseSet", stride),
+            &sparse_set,
+            |b, storage| b.iter(|| storage.iter().map(|(_, value)| *value).sum::<u64>()),
+        );
+    }
+    group.finish();
+}
+
+fn lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get");
+    for stride in [1, 10, 100] {
+        let (generational_vec, sparse_set) = populate(stride);
+        let handles = generational_vec.handles().collect::<Vec<_>>();
+        group.bench_with_input(
+            BenchmarkId::new("GenerationalVec", stride),
+            &generational_vec,
+            |b, storage| {
+                b.iter(|| {
+                    handles
+                        .iter()
+                        .filter_map(|handle| storage.get(black_box(*handle)))
+                        .sum::<u64>()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("SparseSet", stride),
+            &sparse_set,
+            |b, storage| {
+                b.iter(|| {
+                    handles
+                        .iter()
+                        .filter_map(|handle| storage.get(black_box(*handle)))
+                        .sum::<u64>()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn insert_and_remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_remove");
+    let mut allocator = HandleAllocator::new();
+    let handles = (0..HANDLES)
+        .map(|_| allocator.allocate())
+        .collect::<Vec<_>>();
+    group.bench_function("GenerationalVec", |b| {
+        b.iter(|| {
+            let mut storage = GenerationalVec::new(SlotVec::default());
+            for handle in handles.iter() {
+                storage.insert(*handle, 0_u64).unwrap();
+            }
+            for handle in handles.iter().step_by(2) {
+                storage.remove(*handle);
+            }
+            storage
+        })
+    });
+    group.bench_function("SparseSet", |b| {
+        b.iter(|| {
+            let mut storage = SparseSet::new();
+            for handle in handles.iter() {
+                storage.insert(*handle, 0_u64).unwrap();
+            }
+            for handle in handles.iter().step_by(2) {
+                storage.remove(*handle);
+            }
+            storage
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, iteration, lookup, insert_and_remove);
+criterion_main!(benches);
@@ -1,12 +1,114 @@
+/// How the app and its window are set up at launch
+#[derive(Debug, Clone)]
+pub struct LaunchConfig {
+    pub window: WindowConfig,
+    /// Closes the app when pressed, unless the gui consumes it
+    pub exit_key: Option<winit::keyboard::KeyCode>,
+}
+
+impl Default for LaunchConfig {
+    fn default() -> Self {
+        Self {
+            window: WindowConfig::default(),
+            exit_key: Some(winit::keyboard::KeyCode::Escape),
+        }
+    }
+}
+
+impl LaunchConfig {
+    fn is_exit_key(&self, key_code: winit::keyboard::KeyCode) -> bool {
+        self.exit_key == Some(key_code)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowConfig {
+    /// Falls back to `App::title`
+    pub title: Option<String>,
+    /// The initial inner size in logical pixels, also used for the canvas on the web
+    pub size: (u32, u32),
+    pub min_size: Option<(u32, u32)>,
+    pub resizable: bool,
+    pub fullscreen: Option<FullscreenMode>,
+    /// Only modes the surface supports are used, with vsync as the fallback
+    pub present_mode: wgpu::PresentMode,
+    /// The id of the canvas element to render to on the web
+    pub canvas_id: String,
+    pub decorations: bool,
+    pub icon: Option<WindowIcon>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: None,
+            size: (1280, 720),
+            min_size: None,
+            resizable: true,
+            fullscreen: None,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            canvas_id: "canvas".to_string(),
+            decorations: true,
+            icon: None,
+        }
+    }
+}
+
+impl WindowConfig {
+    fn title<'a>(&'a self, app: &'a impl App) -> &'a str {
+        self.title.as_deref().unwrap_or(app.title())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FullscreenMode {
+    /// Covers the current monitor without changing its video mode
+    Borderless,
+    /// Takes over the primary monitor at its largest video mode,
+    /// falling back to borderless where that isn't possible
+    Exclusive,
+}
+
+/// Window icon pixels as 8-bit RGBA rows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowIcon {
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
 pub fn launch_app(state: impl App + 'static) {
+    launch_app_with(state, LaunchConfig::default());
+}
+
+pub fn launch_app_with(state: impl App + 'static, config: LaunchConfig) {
     let event_loop = winit::event_loop::EventLoopBuilder::with_user_event()
         .build()
         .expect("Failed to create event loop");
-    let mut window_builder = winit::window::WindowBuilder::new();
 
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        window_builder = window_builder.with_title("Standalone Winit/Wgpu Example");
+    let window_config = &config.window;
+    let (width, height) = window_config.size;
+    let mut window_builder = winit::window::WindowBuilder::new()
+        .with_title(window_config.title(&state))
+        .with_inner_size(winit::dpi::LogicalSize::new(width, height))
+        .with_resizable(window_config.resizable)
+        .with_decorations(window_config.decorations)
+        .with_fullscreen(
+            window_config
+                .fullscreen
+                .map(|mode| fullscreen(&event_loop, mode)),
+        );
+
+    if let Some((width, height)) = window_config.min_size {
+        window_builder =
+            window_builder.with_min_inner_size(winit::dpi::LogicalSize::new(width, height));
+    }
+
+    if let Some(icon) = window_config.icon.clone() {
+        match winit::window::Icon::from_rgba(icon.rgba, icon.width, icon.height) {
+            Ok(icon) => window_builder = window_builder.with_window_icon(Some(icon)),
+            Err(error) => log::error!("Failed to load window icon: {error}"),
+        }
     }
 
     #[cfg(target_arch = "wasm32")]
@@ -17,7 +119,7 @@ pub fn launch_app(state: impl App + 'static) {
             .unwrap()
             .document()
             .unwrap()
-            .get_element_by_id("canvas")
+            .get_element_by_id(&window_config.canvas_id)
             .unwrap()
             .dyn_into::<web_sys::HtmlCanvasElement>()
             .unwrap();
@@ -31,14 +133,38 @@ pub fn launch_app(state: impl App + 'static) {
     #[cfg(not(target_arch = "wasm32"))]
     {
         env_logger::init();
-        pollster::block_on(run_app(event_loop, window, state));
+        pollster::block_on(run_app(event_loop, window, state, config));
     }
 
     #[cfg(target_arch = "wasm32")]
     {
         std::panic::set_hook(Box::new(console_error_panic_hook::hook));
         console_log::init().expect("could not initialize logger");
-        wasm_bindgen_futures::spawn_local(run_app(event_loop, window, state));
+        wasm_bindgen_futures::spawn_local(run_app(event_loop, window, state, config));
+    }
+}
+
+fn fullscreen(
+    event_loop: &winit::event_loop::EventLoop<()>,
+    mode: FullscreenMode,
+) -> winit::window::Fullscreen {
+    let video_mode = event_loop
+        .primary_monitor()
+        .or_else(|| event_loop.available_monitors().next())
+        .and_then(|monitor| {
+            monitor.video_modes().max_by_key(|video_mode| {
+                let size = video_mode.size();
+                (
+                    size.width * size.height,
+                    video_mode.refresh_rate_millihertz(),
+                )
+            })
+        });
+    match (mode, video_mode) {
+        (FullscreenMode::Exclusive, Some(video_mode)) => {
+            winit::window::Fullscreen::Exclusive(video_mode)
+        }
+        _ => winit::window::Fullscreen::Borderless(None),
     }
 }
 
@@ -46,6 +172,7 @@ async fn run_app(
     event_loop: winit::event_loop::EventLoop<()>,
     window: winit::window::Window,
     mut state: impl App + 'static,
+    config: LaunchConfig,
 ) {
     let window = std::sync::Arc::new(window);
 
@@ -65,31 +192,56 @@ async fn run_app(
     event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
 
     #[cfg(not(target_arch = "wasm32"))]
-    let (width, height) = (window.inner_size().width, window.inner_size().height.min(1));
+    let (width, height) = (
+        window.inner_size().width.max(1),
+        window.inner_size().height.max(1),
+    );
 
+    // The canvas may not be laid out yet, so the configured logical size is scaled instead
     #[cfg(target_arch = "wasm32")]
-    let (width, height) = (1280, 720);
+    let (width, height) = {
+        let (width, height) = config.window.size;
+        let size =
+            winit::dpi::LogicalSize::new(width, height).to_physical::<u32>(window.scale_factor());
+        (size.width.max(1), size.height.max(1))
+    };
 
-    let mut renderer = crate::graphics::Renderer::new(window.clone(), width, height).await;
+    let mut renderer =
+        crate::graphics::Renderer::new(window.clone(), width, height, config.window.present_mode)
+            .await;
 
     let mut last_render_time = crate::Instant::now();
 
-    let mut context = Context {
-        io: Io::default(),
-        delta_time: crate::Duration::default(),
-    };
+    let mut context = Context::new();
+    context.clipboard = crate::text::Clipboard::system(
+        winit::raw_window_handle::HasDisplayHandle::display_handle(&*window)
+            .ok()
+            .map(|handle| handle.as_raw()),
+    );
     state.initialize(&mut context);
 
     event_loop
         .run(move |event, elwt| {
+            // Receive gui window event
+            let consumed_by_gui = match event {
+                winit::event::Event::WindowEvent { ref event, .. } => {
+                    gui_state.on_window_event(&window, event).consumed
+                }
+                _ => false,
+            };
+
+            context.io.receive_event_after_gui(
+                &event,
+                window_center(window.inner_size()),
+                GuiCapture::from_context(gui_state.egui_ctx()),
+            );
             state.receive_event(&mut context, &event);
 
             match event {
                 winit::event::Event::AboutToWait => window.request_redraw(),
 
                 winit::event::Event::WindowEvent { ref event, .. } => {
-                    // Receive gui window event
-                    if gui_state.on_window_event(&window, event).consumed {
+                    if consumed_by_gui {
                         return;
                     }
 
@@ -102,12 +254,7 @@ async fn run_app(
                                     ..
                                 },
                             ..
-                        } => {
-                            // Exit by pressing the escape key
-                            if matches!(key_code, winit::keyboard::KeyCode::Escape) {
-                                elwt.exit();
-                            }
-                        }
+                        } if config.is_exit_key(*key_code) => elwt.exit(),
 
                         // Close button handler
                         winit::event::WindowEvent::CloseRequested => {
@@ -133,15 +280,31 @@ async fn run_app(
                             let gui_input = gui_state.take_egui_input(&window);
                             gui_state.egui_ctx().begin_frame(gui_input);
 
-                            state.update(&mut context, gui_state.egui_ctx());
+                            update_frame(&mut state, &mut context, gui_state.egui_ctx(), now);
+                            if context.exit_requested() {
+                                elwt.exit();
+                            }
 
                             let egui::FullOutput {
+                                mut platform_output,
                                 textures_delta,
                                 shapes,
                                 pixels_per_point,
                                 ..
                             } = gui_state.egui_ctx().end_frame();
 
+                            // Keep the IME enabled for the app's own text entry
+                            if platform_output.ime.is_none() {
+                                platform_output.ime =
+                                    context
+                                        .text_input_area()
+                                        .map(|rect| egui::output::IMEOutput {
+                                            rect,
+                                            cursor_rect: rect,
+                                        });
+                            }
+                            gui_state.handle_platform_output(&window, platform_output);
+
                             let paint_jobs =
                                 gui_state.egui_ctx().tessellate(shapes, pixels_per_point);
 
@@ -157,7 +320,7 @@ async fn run_app(
                             #[cfg(target_arch = "wasm32")]
                             let screen_descriptor = {
                                 egui_wgpu::ScreenDescriptor {
-                                    size_in_pixels: [1280, 720],
+                                    size_in_pixels: [width, height],
                                     pixels_per_point: window.scale_factor() as f32,
                                 }
                             };
@@ -180,6 +343,39 @@ async fn run_app(
         .unwrap();
 }
 
+/// Runs the simulation for one frame, shared by every runner:
+/// gamepads, fixed updates, then `App::update`, commands, systems and finally events
+pub(crate) fn update_frame(
+    state: &mut impl App,
+    context: &mut Context,
+    ui: &egui::Context,
+    now: crate::Instant,
+) {
+    context.io.gamepads.update(now);
+
+    for _ in 0..context.fixed_timestep.advance(context.delta_time) {
+        state.fixed_update(context);
+        if let Err(error) = context
+            .schedule
+            .run_stage(crate::schedule::Stage::FixedUpdate, &mut context.world)
+        {
+            log::error!("Failed to run fixed systems: {error}");
+        }
+    }
+
+    state.update(context, ui);
+
+    if let Err(error) = context.apply_commands() {
+        log::error!("Failed to apply commands: {error}");
+    }
+
+    if let Err(error) = context.schedule.run(&mut context.world) {
+        log::error!("Failed to run systems: {error}");
+    }
+    context.world.update_events();
+    context.world.update_removed_components();
+}
+
 pub trait App {
     fn title(&self) -> &str {
         "Nightmare"
@@ -191,19 +387,135 @@ pub trait App {
     /// Called when a winit event is received
     fn receive_event(&mut self, _context: &mut Context, _event: &winit::event::Event<()>) {}
 
-    /// Called every frame prior to rendering
+    /// Called once for every elapsed fixed timestep, so zero or more times per frame
+    /// before `update`. Each call is followed by the `FixedUpdate` systems.
+    /// Commands recorded in the context are only applied after the next `update`,
+    /// so those systems don't see them yet.
+    fn fixed_update(&mut self, _context: &mut Context) {}
+
+    /// Called every frame prior to rendering, before the scheduled systems run.
+    /// Commands recorded in the context are applied as soon as it returns.
     fn update(&mut self, _context: &mut Context, _ui: &egui::Context) {}
 }
 
 pub struct Context {
     pub io: Io,
     pub delta_time: crate::Duration,
+    /// Drives `App::fixed_update`, at 60 Hz unless changed
+    pub fixed_timestep: crate::time::FixedTimestep,
+    /// Applied to the world after every `App::update`, which is also when
+    /// commands recorded in `fixed_update` take effect. Systems only get the world,
+    /// so they apply their own `World::commands` before returning.
+    pub commands: crate::command::Commands,
+    world: crate::world::World,
+    pub schedule: crate::schedule::Schedule,
+    exit_requested: bool,
+    clipboard: crate::text::Clipboard,
+    text_input_area: Option<egui::Rect>,
+}
+
+impl Context {
+    /// A context with an empty world and the built in systems
+    pub(crate) fn new() -> Self {
+        let world = crate::world::World::default();
+        let mut context = Self {
+            io: Io::default(),
+            delta_time: crate::Duration::default(),
+            fixed_timestep: crate::time::FixedTimestep::default(),
+            commands: world.commands(),
+            world,
+            schedule: crate::schedule::Schedule::default(),
+            exit_requested: false,
+            clipboard: crate::text::Clipboard::default(),
+            text_input_area: None,
+        };
+        context.schedule.add_system(
+            crate::schedule::Stage::PostUpdate,
+            "propagate_transforms",
+            crate::hierarchy::propagate_transforms,
+        );
+        context
+    }
+
+    pub fn world(&self) -> &crate::world::World {
+        &self.world
+    }
+
+    /// The world, which should be swapped out with `set_world`
+    /// so that `commands` keep reserving entities from it
+    pub fn world_mut(&mut self) -> &mut crate::world::World {
+        &mut self.world
+    }
+
+    /// Applies the recorded commands right away instead of after `App::update`
+    pub fn apply_commands(&mut self) -> crate::genvec::Result<()> {
+        self.commands.apply(&mut self.world)
+    }
+
+    /// Replaces the world, returning the previous one.
+    /// Pending commands are applied to the previous world first.
+    pub fn set_world(
+        &mut self,
+        world: crate::world::World,
+    ) -> crate::genvec::Result<crate::world::World> {
+        let result = self.apply_commands();
+        let previous = std::mem::replace(&mut self.world, world);
+        self.commands = self.world.commands();
+        result.map(|_| previous)
+    }
+
+    /// Stops the app once the current frame is done
+    pub fn exit(&mut self) {
+        self.exit_requested = true;
+    }
+
+    pub fn exit_requested(&self) -> bool {
+        self.exit_requested
+    }
+
+    /// How far the simulation is into the next fixed step, from 0 to 1,
+    /// for interpolating rendered state between fixed updates
+    pub fn alpha(&self) -> f32 {
+        self.fixed_timestep.alpha()
+    }
+
+    /// The clipboard's text, or `None` if it holds something else or can't be read
+    pub fn clipboard_text(&mut self) -> Option<String> {
+        self.clipboard.get()
+    }
+
+    pub fn set_clipboard_text(&mut self, text: impl Into<String>) {
+        self.clipboard.set(text);
+    }
+
+    /// Enables the IME for text entry outside of gui widgets,
+    /// placing its candidate window near `cursor_area`, in gui points.
+    /// Gui text fields still take over the IME while they have focus.
+    pub fn start_text_input(&mut self, cursor_area: egui::Rect) {
+        self.text_input_area = Some(cursor_area);
+    }
+
+    pub fn stop_text_input(&mut self) {
+        self.text_input_area = None;
+    }
+
+    pub fn text_input_area(&self) -> Option<egui::Rect> {
+        self.text_input_area
+    }
 }
 
 #[derive(Default)]
 pub struct Io {
     pub keystates: std::collections::HashMap<winit::keyboard::KeyCode, winit::event::ElementState>,
+    /// Per-frame transitions of every key
+    pub keys: crate::input::ButtonInput<winit::keyboard::KeyCode>,
     pub mouse: Mouse,
+    /// Connected gamepads, which stay empty until a backend is set
+    pub gamepads: crate::gamepad::Gamepads,
+    /// The bindings behind `is_action_pressed`, `axis` and `dual_axis`
+    pub input_map: crate::input::InputMap,
+    /// Typed text, IME composition and logical keys
+    pub text_input: crate::text::TextInput,
 }
 
 impl Io {
@@ -212,36 +524,174 @@ impl Io {
             && self.keystates[&keycode] == winit::event::ElementState::Pressed
     }
 
+    pub fn is_key_just_pressed(&self, keycode: winit::keyboard::KeyCode) -> bool {
+        self.keys.just_pressed(keycode)
+    }
+
+    pub fn is_key_just_released(&self, keycode: winit::keyboard::KeyCode) -> bool {
+        self.keys.just_released(keycode)
+    }
+
+    /// Clears the per-frame transitions and deltas, with `time` as the start of the frame.
+    /// Runners call this once per frame before delivering that frame's input.
+    pub fn new_frame(&mut self, time: crate::Instant) {
+        self.keys.new_frame(time);
+        self.mouse.new_frame(time);
+        self.text_input.new_frame();
+    }
+
+    /// Receives an event that arrived just now
     pub fn receive_event<T>(
         &mut self,
         event: &winit::event::Event<T>,
         window_center: nalgebra_glm::Vec2,
     ) {
-        if let winit::event::Event::WindowEvent {
-            event:
-                winit::event::WindowEvent::KeyboardInput {
-                    event:
-                        winit::event::KeyEvent {
-                            physical_key: winit::keyboard::PhysicalKey::Code(key_code),
-                            state,
-                            ..
-                        },
-                    ..
-                },
-            ..
-        } = *event
-        {
+        self.receive_event_at(event, window_center, crate::Instant::now());
+    }
+
+    /// Receives an event that arrived at `time`, which also starts the frame on `NewEvents`
+    pub fn receive_event_at<T>(
+        &mut self,
+        event: &winit::event::Event<T>,
+        window_center: nalgebra_glm::Vec2,
+        time: crate::Instant,
+    ) {
+        match event {
+            winit::event::Event::NewEvents { .. } => {
+                self.new_frame(time);
+                return;
+            }
+            winit::event::Event::WindowEvent { event, .. } => match event {
+                winit::event::WindowEvent::KeyboardInput { event, .. } => {
+                    self.receive_key_input_at(event.into(), time)
+                }
+                winit::event::WindowEvent::Ime(ime) => {
+                    self.text_input.receive_ime_event(ime.into())
+                }
+                // Releases are never seen while unfocused, so nothing can stay held
+                winit::event::WindowEvent::Focused(false) => {
+                    self.keystates.clear();
+                    self.keys.release_all();
+                    self.text_input.release_all();
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        self.mouse.receive_event_at(event, window_center, time);
+    }
+
+    /// Receives a keyboard event pressed or released just now
+    pub fn receive_key_input(&mut self, input: crate::text::KeyInput) {
+        self.receive_key_input_at(input, crate::Instant::now());
+    }
+
+    /// Receives a keyboard event pressed or released at `time`,
+    /// which is how keys are fed in tests
+    pub fn receive_key_input_at(&mut self, input: crate::text::KeyInput, time: crate::Instant) {
+        if let winit::keyboard::PhysicalKey::Code(key_code) = input.physical_key {
+            let state = input.state;
             *self.keystates.entry(key_code).or_insert(state) = state;
+            match state {
+                winit::event::ElementState::Pressed => self.keys.press(key_code, time),
+                winit::event::ElementState::Released => self.keys.release(key_code),
+            }
+        }
+        self.text_input.receive_key_input(input);
+    }
+
+    /// Receives an event the gui has already seen.
+    ///
+    /// While the gui captures the keyboard, key presses and IME text are kept from the app,
+    /// and while it captures the pointer, clicks, scrolling and cursor motion are,
+    /// so typing in a text field or dragging a slider doesn't also drive the game.
+    /// Releases always get through, so nothing stays held after the gui takes focus.
+    pub fn receive_event_after_gui<T>(
+        &mut self,
+        event: &winit::event::Event<T>,
+        window_center: nalgebra_glm::Vec2,
+        gui: GuiCapture,
+    ) {
+        self.receive_event_after_gui_at(event, window_center, gui, crate::Instant::now());
+    }
+
+    /// Receives an event the gui has already seen that arrived at `time`
+    pub fn receive_event_after_gui_at<T>(
+        &mut self,
+        event: &winit::event::Event<T>,
+        window_center: nalgebra_glm::Vec2,
+        gui: GuiCapture,
+        time: crate::Instant,
+    ) {
+        let winit::event::Event::WindowEvent {
+            event: window_event,
+            ..
+        } = event
+        else {
+            self.receive_event_at(event, window_center, time);
+            return;
+        };
+        match *window_event {
+            winit::event::WindowEvent::KeyboardInput {
+                event:
+                    winit::event::KeyEvent {
+                        state: winit::event::ElementState::Pressed,
+                        ..
+                    },
+                ..
+            }
+            | winit::event::WindowEvent::Ime(
+                winit::event::Ime::Preedit(..) | winit::event::Ime::Commit(_),
+            ) if gui.keyboard => {}
+            winit::event::WindowEvent::MouseInput {
+                state: winit::event::ElementState::Pressed,
+                ..
+            }
+            | winit::event::WindowEvent::MouseWheel { .. }
+                if gui.pointer => {}
+            winit::event::WindowEvent::CursorMoved { position, .. } if gui.pointer => {
+                self.mouse.track_cursor(position, window_center)
+            }
+            _ => self.receive_event_at(event, window_center, time),
         }
-        self.mouse.receive_event(event, window_center);
     }
 }
 
+/// Which devices the gui is using, so the app doesn't also act on their input
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuiCapture {
+    /// A text field or other widget has keyboard focus
+    pub keyboard: bool,
+    /// The pointer is over the gui or dragging one of its widgets
+    pub pointer: bool,
+}
+
+impl GuiCapture {
+    pub const NONE: Self = Self {
+        keyboard: false,
+        pointer: false,
+    };
+
+    pub fn from_context(context: &egui::Context) -> Self {
+        Self {
+            keyboard: context.wants_keyboard_input(),
+            pointer: context.wants_pointer_input(),
+        }
+    }
+}
+
+/// The center of a window in physical pixels, matching cursor positions
+pub(crate) fn window_center(size: winit::dpi::PhysicalSize<u32>) -> nalgebra_glm::Vec2 {
+    nalgebra_glm::vec2(size.width as f32 / 2.0, size.height as f32 / 2.0)
+}
+
 #[derive(Default)]
 pub struct Mouse {
     pub is_left_clicked: bool,
     pub is_middle_clicked: bool,
     pub is_right_clicked: bool,
+    /// Per-frame transitions of every mouse button
+    pub buttons: crate::input::ButtonInput<winit::event::MouseButton>,
     pub position: nalgebra_glm::Vec2,
     pub position_delta: nalgebra_glm::Vec2,
     pub offset_from_center: nalgebra_glm::Vec2,
@@ -251,16 +701,27 @@ pub struct Mouse {
 }
 
 impl Mouse {
+    /// Receives an event that arrived just now
     pub fn receive_event<T>(
         &mut self,
         event: &winit::event::Event<T>,
         window_center: nalgebra_glm::Vec2,
+    ) {
+        self.receive_event_at(event, window_center, crate::Instant::now());
+    }
+
+    /// Receives an event that arrived at `time`, which also starts the frame on `NewEvents`
+    pub fn receive_event_at<T>(
+        &mut self,
+        event: &winit::event::Event<T>,
+        window_center: nalgebra_glm::Vec2,
+        time: crate::Instant,
     ) {
         match event {
-            winit::event::Event::NewEvents { .. } => self.new_events(),
+            winit::event::Event::NewEvents { .. } => self.new_frame(time),
             winit::event::Event::WindowEvent { event, .. } => match *event {
                 winit::event::WindowEvent::MouseInput { button, state, .. } => {
-                    self.mouse_input(button, state)
+                    self.mouse_input(button, state, time)
                 }
                 winit::event::WindowEvent::CursorMoved { position, .. } => {
                     self.cursor_moved(position, window_center)
@@ -269,13 +730,22 @@ impl Mouse {
                     delta: winit::event::MouseScrollDelta::LineDelta(h_lines, v_lines),
                     ..
                 } => self.mouse_wheel(h_lines, v_lines),
+                winit::event::WindowEvent::Focused(false) => self.release_all(),
                 _ => {}
             },
             _ => {}
         }
     }
 
-    fn new_events(&mut self) {
+    /// Whether a mouse button was double-clicked this frame
+    pub fn is_double_clicked(&self, button: winit::event::MouseButton) -> bool {
+        self.buttons.double_pressed(button)
+    }
+
+    /// Clears the per-frame transitions and deltas, with `time` as the start of the frame
+    pub fn new_frame(&mut self, time: crate::Instant) {
+        self.buttons.new_frame(time);
+
         if !self.scrolled {
             self.wheel_delta = nalgebra_glm::vec2(0.0, 0.0);
         }
@@ -287,6 +757,23 @@ impl Mouse {
         self.moved = false;
     }
 
+    /// Follows the cursor without reporting it as moved
+    fn track_cursor(
+        &mut self,
+        position: winit::dpi::PhysicalPosition<f64>,
+        window_center: nalgebra_glm::Vec2,
+    ) {
+        self.position = nalgebra_glm::vec2(position.x as _, position.y as _);
+        self.offset_from_center = window_center - self.position;
+    }
+
+    fn release_all(&mut self) {
+        self.is_left_clicked = false;
+        self.is_middle_clicked = false;
+        self.is_right_clicked = false;
+        self.buttons.release_all();
+    }
+
     fn cursor_moved(
         &mut self,
         position: winit::dpi::PhysicalPosition<f64>,
@@ -310,8 +797,14 @@ impl Mouse {
         &mut self,
         button: winit::event::MouseButton,
         state: winit::event::ElementState,
+        time: crate::Instant,
     ) {
         let clicked = state == winit::event::ElementState::Pressed;
+        if clicked {
+            self.buttons.press(button, time);
+        } else {
+            self.buttons.release(button);
+        }
         match button {
             winit::event::MouseButton::Left => self.is_left_clicked = clicked,
             winit::event::MouseButton::Middle => self.is_middle_clicked = clicked,
@@ -320,3 +813,265 @@ impl Mouse {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CENTER: nalgebra_glm::Vec2 = nalgebra_glm::Vec2::new(400.0, 300.0);
+
+    fn window_event(event: winit::event::WindowEvent) -> winit::event::Event<()> {
+        winit::event::Event::WindowEvent {
+            window_id: unsafe { winit::window::WindowId::dummy() },
+            event,
+        }
+    }
+
+    fn mouse_input(state: winit::event::ElementState) -> winit::event::Event<()> {
+        window_event(winit::event::WindowEvent::MouseInput {
+            device_id: unsafe { winit::event::DeviceId::dummy() },
+            state,
+            button: winit::event::MouseButton::Left,
+        })
+    }
+
+    fn cursor_moved(x: f64, y: f64) -> winit::event::Event<()> {
+        window_event(winit::event::WindowEvent::CursorMoved {
+            device_id: unsafe { winit::event::DeviceId::dummy() },
+            position: winit::dpi::PhysicalPosition::new(x, y),
+        })
+    }
+
+    fn new_events() -> winit::event::Event<()> {
+        winit::event::Event::NewEvents(winit::event::StartCause::Poll)
+    }
+
+    const KEYBOARD: GuiCapture = GuiCapture {
+        keyboard: true,
+        pointer: false,
+    };
+    const POINTER: GuiCapture = GuiCapture {
+        keyboard: false,
+        pointer: true,
+    };
+    const ALL: GuiCapture = GuiCapture {
+        keyboard: true,
+        pointer: true,
+    };
+
+    #[test]
+    fn events_reach_io() {
+        let mut io = Io::default();
+        let now = crate::Instant::now();
+        let center = window_center(winit::dpi::PhysicalSize::new(800, 600));
+        assert_eq!(center, CENTER);
+
+        io.receive_event_after_gui_at(&new_events(), center, GuiCapture::NONE, now);
+        io.receive_event_after_gui_at(&cursor_moved(410.0, 280.0), center, GuiCapture::NONE, now);
+        io.receive_event_after_gui_at(
+            &mouse_input(winit::event::ElementState::Pressed),
+            center,
+            GuiCapture::NONE,
+            now,
+        );
+
+        assert!(io.mouse.is_left_clicked);
+        assert!(io
+            .mouse
+            .buttons
+            .just_pressed(winit::event::MouseButton::Left));
+        assert_eq!(io.mouse.position, nalgebra_glm::vec2(410.0, 280.0));
+        assert_eq!(io.mouse.offset_from_center, nalgebra_glm::vec2(-10.0, 20.0));
+    }
+
+    #[test]
+    fn the_gui_keeps_consumed_pointer_input() {
+        let mut io = Io::default();
+        let now = crate::Instant::now();
+        io.receive_event_after_gui_at(&new_events(), CENTER, GuiCapture::NONE, now);
+        io.receive_event_after_gui_at(&cursor_moved(10.0, 10.0), CENTER, GuiCapture::NONE, now);
+
+        io.receive_event_after_gui_at(&new_events(), CENTER, POINTER, now);
+        let position_delta = io.mouse.position_delta;
+        io.receive_event_after_gui_at(&cursor_moved(50.0, 10.0), CENTER, POINTER, now);
+        io.receive_event_after_gui_at(
+            &mouse_input(winit::event::ElementState::Pressed),
+            CENTER,
+            POINTER,
+            now,
+        );
+        io.receive_event_after_gui_at(
+            &window_event(winit::event::WindowEvent::MouseWheel {
+                device_id: unsafe { winit::event::DeviceId::dummy() },
+                delta: winit::event::MouseScrollDelta::LineDelta(0.0, 1.0),
+                phase: winit::event::TouchPhase::Moved,
+            }),
+            CENTER,
+            POINTER,
+            now,
+        );
+
+        // The cursor is still followed, but the app sees no motion, click or scroll
+        assert_eq!(io.mouse.position, nalgebra_glm::vec2(50.0, 10.0));
+        assert_eq!(io.mouse.position_delta, position_delta);
+        assert!(!io.mouse.moved);
+        assert!(!io.mouse.is_left_clicked);
+        assert_eq!(io.mouse.wheel_delta, nalgebra_glm::Vec2::zeros());
+
+        // Releases get through even when consumed
+        io.receive_event_after_gui_at(&new_events(), CENTER, GuiCapture::NONE, now);
+        io.receive_event_after_gui_at(
+            &mouse_input(winit::event::ElementState::Pressed),
+            CENTER,
+            GuiCapture::NONE,
+            now,
+        );
+        io.receive_event_after_gui_at(
+            &mouse_input(winit::event::ElementState::Released),
+            CENTER,
+            POINTER,
+            now,
+        );
+        assert!(!io.mouse.is_left_clicked);
+        assert!(io
+            .mouse
+            .buttons
+            .just_released(winit::event::MouseButton::Left));
+    }
+
+    #[test]
+    fn the_gui_only_keeps_input_from_devices_it_captures() {
+        let mut io = Io::default();
+        let now = crate::Instant::now();
+        let commit = window_event(winit::event::WindowEvent::Ime(winit::event::Ime::Commit(
+            "game".into(),
+        )));
+
+        // Typing in a text field doesn't stop clicks
+        io.receive_event_after_gui_at(
+            &mouse_input(winit::event::ElementState::Pressed),
+            CENTER,
+            KEYBOARD,
+            now,
+        );
+        assert!(io.mouse.is_left_clicked);
+
+        // Hovering the gui doesn't stop typing
+        io.receive_event_after_gui_at(&commit, CENTER, POINTER, now);
+        assert_eq!(io.text(), "game");
+    }
+
+    #[test]
+    fn the_gui_keeps_consumed_text() {
+        let mut io = Io::default();
+        let now = crate::Instant::now();
+        let commit = |text: &str| {
+            window_event(winit::event::WindowEvent::Ime(winit::event::Ime::Commit(
+                text.into(),
+            )))
+        };
+        io.receive_event_after_gui_at(&commit("gui"), CENTER, KEYBOARD, now);
+        io.receive_event_after_gui_at(&commit("game"), CENTER, GuiCapture::NONE, now);
+        assert_eq!(io.text(), "game");
+
+        let mut context = Context::new();
+        context.set_clipboard_text(io.text());
+        assert_eq!(context.clipboard_text().as_deref(), Some("game"));
+    }
+
+    #[test]
+    fn losing_focus_releases_everything() {
+        let mut io = Io::default();
+        let now = crate::Instant::now();
+        io.keystates.insert(
+            winit::keyboard::KeyCode::KeyW,
+            winit::event::ElementState::Pressed,
+        );
+        io.keys.press(winit::keyboard::KeyCode::KeyW, now);
+        io.receive_event_after_gui_at(
+            &mouse_input(winit::event::ElementState::Pressed),
+            CENTER,
+            GuiCapture::NONE,
+            now,
+        );
+
+        io.receive_event_after_gui_at(
+            &window_event(winit::event::WindowEvent::Focused(false)),
+            CENTER,
+            ALL,
+            now,
+        );
+        assert!(!io.is_key_pressed(winit::keyboard::KeyCode::KeyW));
+        assert!(io.is_key_just_released(winit::keyboard::KeyCode::KeyW));
+        assert!(!io.mouse.is_left_clicked);
+    }
+
+    struct Titled;
+
+    impl App for Titled {
+        fn title(&self) -> &str {
+            "Titled"
+        }
+    }
+
+    #[test]
+    fn launch_config_defaults() {
+        let config = LaunchConfig::default();
+        assert!(config.is_exit_key(winit::keyboard::KeyCode::Escape));
+        assert!(!config.is_exit_key(winit::keyboard::KeyCode::Enter));
+
+        let window = config.window;
+        assert_eq!(window.title, None);
+        assert_eq!(window.size, (1280, 720));
+        assert_eq!(window.min_size, None);
+        assert!(window.resizable);
+        assert_eq!(window.fullscreen, None);
+        assert_eq!(window.present_mode, wgpu::PresentMode::AutoVsync);
+        assert_eq!(window.canvas_id, "canvas");
+        assert!(window.decorations);
+        assert_eq!(window.icon, None);
+    }
+
+    #[test]
+    fn no_exit_key_never_exits() {
+        let config = LaunchConfig {
+            exit_key: None,
+            ..Default::default()
+        };
+        assert!(!config.is_exit_key(winit::keyboard::KeyCode::Escape));
+    }
+
+    #[test]
+    fn window_titles_fall_back_to_the_app() {
+        let mut window = WindowConfig::default();
+        assert_eq!(window.title(&Titled), "Titled");
+
+        window.title = Some("Configured".to_string());
+        assert_eq!(window.title(&Titled), "Configured");
+
+        struct Untitled;
+        impl App for Untitled {}
+        assert_eq!(WindowConfig::default().title(&Untitled), "Nightmare");
+    }
+
+    #[test]
+    fn commands_follow_a_replaced_world() -> crate::genvec::Result<()> {
+        let mut context = Context::new();
+        let queued = context.commands.spawn();
+        context.commands.add_component(queued, 1_u32);
+
+        let previous = context.set_world(crate::world::World::new())?;
+        assert_eq!(previous.get_component(queued), Some(&1_u32));
+
+        // Entities spawned afterwards are reserved from the new world
+        let mut world = crate::world::World::new();
+        world.spawn();
+        context.set_world(world)?;
+        let spawned = context.commands.spawn();
+        context.commands.add_component(spawned, 2_u32);
+        context.apply_commands()?;
+        assert_eq!(context.world().entities().len(), 2);
+        assert_eq!(context.world().get_component(spawned), Some(&2_u32));
+        Ok(())
+    }
+}
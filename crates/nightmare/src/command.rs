@@ -0,0 +1,219 @@
+use crate::{
+    genvec::{HandleReserver, Result},
+    world::{Entity, World},
+};
+
+type Command = Box<dyn FnOnce(&mut World) -> Result<()>>;
+
+/// Records changes to a world so they can be applied later,
+/// for example while the world is borrowed by a query.
+///
+/// Spawned entities are reserved up front, so their handles can be used
+/// in later commands right away and never alias another entity.
+/// Dropping the commands without applying them releases those reservations.
+pub struct Commands {
+    reserver: HandleReserver,
+    queue: Vec<Command>,
+    /// Entities reserved since the commands were last applied
+    reserved: Vec<Entity>,
+}
+
+impl Commands {
+    pub fn new(world: &World) -> Self {
+        Self {
+            reserver: world.entity_reserver(),
+            queue: Vec::new(),
+            reserved: Vec::new(),
+        }
+    }
+
+    /// Reserves an entity that is spawned when the commands are applied
+    pub fn spawn(&mut self) -> Entity {
+        let entity = self.reserver.reserve();
+        self.reserved.push(entity);
+        entity
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.despawn(entity);
+            Ok(())
+        });
+    }
+
+    pub fn despawn_recursive(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.despawn_recursive(entity);
+            Ok(())
+        });
+    }
+
+    pub fn add_component<T: 'static>(&mut self, entity: Entity, component: T) {
+        self.add(move |world| world.add_component(entity, component));
+    }
+
+    pub fn remove_component<T: 'static>(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.remove_component::<T>(entity);
+            Ok(())
+        });
+    }
+
+    pub fn insert_resource<T: 'static>(&mut self, resource: T) {
+        self.add(move |world| {
+            world.insert_resource(resource);
+            Ok(())
+        });
+    }
+
+    /// Records an arbitrary change to the world
+    pub fn add(&mut self, command: impl FnOnce(&mut World) -> Result<()> + 'static) {
+        self.queue.push(Box::new(command));
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Applies every recorded command in order.
+    /// A failing command does not stop the rest, and the first error is returned.
+    pub fn apply(&mut self, world: &mut World) -> Result<()> {
+        world.flush_reserved_entities();
+        self.reserved.clear();
+        let mut result = Ok(());
+        for command in self.queue.drain(..) {
+            if let Err(error) = command(world) {
+                if result.is_ok() {
+                    result = Err(error);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Drop for Commands {
+    fn drop(&mut self) {
+        for entity in self.reserved.drain(..) {
+            self.reserver.release(entity);
+        }
+    }
+}
+
+impl World {
+    /// A command buffer that reserves its entities from this world
+    pub fn commands(&self) -> Commands {
+        Commands::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hierarchy::Parent;
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+
+    #[derive(Debug, PartialEq)]
+    struct Spawner;
+
+    #[test]
+    fn spawning_during_iteration() -> Result<()> {
+        let mut world = World::new();
+        for _ in 0..3 {
+            let spawner = world.spawn();
+            world.add_component(spawner, Spawner)?;
+        }
+
+        let mut commands = world.commands();
+        let mut spawned = Vec::new();
+        for (spawner, _) in world.query::<(Entity, &Spawner)>() {
+            let entity = commands.spawn();
+            commands.add_component(entity, Health(10));
+            commands.add_component(entity, Parent(spawner));
+            spawned.push(entity);
+        }
+
+        // Reserved entities are not alive until the commands are applied
+        assert!(spawned.iter().all(|entity| !world.is_alive(*entity)));
+        assert_eq!(commands.len(), 6);
+        commands.apply(&mut world)?;
+        assert!(commands.is_empty());
+
+        assert_eq!(world.entities().len(), 6);
+        for entity in spawned {
+            assert_eq!(world.get_component(entity), Some(&Health(10)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn commands_apply_in_order() -> Result<()> {
+        let mut world = World::new();
+        let existing = world.spawn();
+        world.add_component(existing, Health(1))?;
+
+        let mut commands = world.commands();
+        let entity = commands.spawn();
+        commands.add_component(entity, Health(2));
+        commands.add_component(entity, Health(3));
+        commands.remove_component::<Health>(existing);
+        commands.add_component(existing, Health(4));
+        commands.despawn(entity);
+        commands.insert_resource(Health(5));
+        commands.apply(&mut world)?;
+
+        assert!(!world.is_alive(entity));
+        assert_eq!(world.get_component(existing), Some(&Health(4)));
+        assert_eq!(world.resource(), Some(&Health(5)));
+        Ok(())
+    }
+
+    #[test]
+    fn reserved_handles_stay_valid() -> Result<()> {
+        let mut world = World::new();
+        let despawned = world.spawn();
+        world.despawn(despawned);
+
+        let mut commands = world.commands();
+        let reserved = commands.spawn();
+        commands.add_component(reserved, Health(1));
+        commands.add_component(despawned, Health(2));
+
+        // Entities spawned directly never reuse a reserved handle
+        let direct = world.spawn();
+        let fresh = world.spawn();
+        assert_ne!(direct, reserved);
+        assert_ne!(fresh, reserved);
+
+        // Commands on dead entities fail without stopping the rest
+        assert!(commands.apply(&mut world).is_err());
+        assert_eq!(world.get_component(reserved), Some(&Health(1)));
+        assert_eq!(world.get_component::<Health>(direct), None);
+        Ok(())
+    }
+
+    #[test]
+    fn dropped_commands_release_their_entities() {
+        let mut world = World::new();
+        let mut commands = world.commands();
+        let reserved = commands.spawn();
+        commands.add_component(reserved, Health(1));
+        drop(commands);
+
+        // Another flush must not bring the dropped reservation to life
+        world.flush_reserved_entities();
+        assert!(!world.is_alive(reserved));
+        assert!(world.entities().is_empty());
+
+        // The slot is reused with a newer generation
+        let spawned = world.spawn();
+        assert_eq!(spawned.index(), reserved.index());
+        assert_ne!(spawned, reserved);
+    }
+}
@@ -0,0 +1,287 @@
+use crate::world::World;
+use std::marker::PhantomData;
+
+/// A double-buffered queue of events of type `T`.
+///
+/// Events stay readable for the frame they are sent in and the frame after,
+/// so every reader that runs once per frame sees each event exactly once.
+/// Stored as a world resource and swapped by `World::update_events`.
+pub struct Events<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+    /// The id of the first event in `previous`
+    previous_start: usize,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            previous_start: 0,
+        }
+    }
+}
+
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+    }
+
+    /// Drops the events sent before the last update
+    /// and starts buffering new events separately
+    pub fn update(&mut self) {
+        self.previous_start += self.previous.len();
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    /// The number of events ever sent, which is the id of the next event
+    pub fn event_count(&self) -> usize {
+        self.current_start() + self.current.len()
+    }
+
+    /// The number of events that are still readable
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.previous_start = self.event_count();
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    fn current_start(&self) -> usize {
+        self.previous_start + self.previous.len()
+    }
+}
+
+/// Sends events into an `Events<T>` queue
+pub struct EventWriter<'w, T> {
+    events: &'w mut Events<T>,
+}
+
+impl<T> EventWriter<'_, T> {
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.events.current.extend(events);
+    }
+}
+
+/// Reads the events of an `Events<T>` queue,
+/// remembering which events it has already seen
+pub struct EventReader<T> {
+    cursor: usize,
+    _event: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        Self {
+            cursor: 0,
+            _event: PhantomData,
+        }
+    }
+}
+
+impl<T> EventReader<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The events sent since this reader last read, oldest first
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> {
+        let skipped = self.cursor.saturating_sub(events.previous_start);
+        self.cursor = events.event_count();
+        events.previous.iter().chain(&events.current).skip(skipped)
+    }
+
+    /// The number of unread events
+    pub fn len(&self, events: &Events<T>) -> usize {
+        events
+            .event_count()
+            .saturating_sub(self.cursor.max(events.previous_start))
+    }
+
+    pub fn is_empty(&self, events: &Events<T>) -> bool {
+        self.len(events) == 0
+    }
+
+    /// The number of events that were dropped before this reader could read them
+    pub fn missed(&self, events: &Events<T>) -> usize {
+        events.previous_start.saturating_sub(self.cursor)
+    }
+
+    /// Marks every event as read without reading them
+    pub fn clear(&mut self, events: &Events<T>) {
+        self.cursor = events.event_count();
+    }
+}
+
+/// The update functions of every event type added to a world
+#[derive(Default)]
+struct EventUpdaters(Vec<fn(&mut World)>);
+
+impl World {
+    /// Adds an `Events<T>` resource that is updated along with the other event types
+    pub fn add_event<T: 'static>(&mut self) {
+        if self.has_resource::<Events<T>>() {
+            return;
+        }
+        self.insert_resource(Events::<T>::new());
+        if !self.has_resource::<EventUpdaters>() {
+            self.insert_resource(EventUpdaters::default());
+        }
+        if let Some(updaters) = self.resource_mut::<EventUpdaters>() {
+            updaters.0.push(|world| {
+                if let Some(events) = world.resource_mut::<Events<T>>() {
+                    events.update();
+                }
+            });
+        }
+    }
+
+    /// Sends an event, adding its event type if needed
+    pub fn send_event<T: 'static>(&mut self, event: T) {
+        self.event_writer().send(event);
+    }
+
+    /// A writer for an event type, adding it if needed
+    pub fn event_writer<T: 'static>(&mut self) -> EventWriter<'_, T> {
+        self.add_event::<T>();
+        EventWriter {
+            events: self
+                .resource_mut::<Events<T>>()
+                .expect("The event type was just added!"),
+        }
+    }
+
+    /// The events sent since the reader last read,
+    /// or nothing if the event type was never added
+    pub fn read_events<'w, T: 'static>(
+        &'w self,
+        reader: &mut EventReader<T>,
+    ) -> impl Iterator<Item = &'w T> {
+        self.resource::<Events<T>>()
+            .map(|events| reader.read(events))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Swaps the buffers of every added event type, run once per frame
+    pub fn update_events(&mut self) {
+        let updaters = self
+            .resource::<EventUpdaters>()
+            .map(|updaters| updaters.0.clone())
+            .unwrap_or_default();
+        for update in updaters {
+            update(self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Collision(u32);
+
+    fn read(world: &World, reader: &mut EventReader<Collision>) -> Vec<u32> {
+        world
+            .read_events(reader)
+            .map(|collision| collision.0)
+            .collect()
+    }
+
+    #[test]
+    fn events_last_for_two_updates() {
+        let mut events = Events::new();
+        events.send(1);
+        events.update();
+        events.send(2);
+        assert_eq!(events.len(), 2);
+
+        let mut reader = EventReader::new();
+        assert_eq!(reader.len(&events), 2);
+        assert_eq!(reader.read(&events).copied().collect::<Vec<_>>(), [1, 2]);
+        assert!(reader.is_empty(&events));
+
+        events.update();
+        assert_eq!(events.len(), 1);
+        events.update();
+        assert!(events.is_empty());
+        assert_eq!(events.event_count(), 2);
+    }
+
+    #[test]
+    fn readers_have_independent_cursors() {
+        let mut world = World::new();
+        world.add_event::<Collision>();
+        let mut physics = EventReader::new();
+        let mut audio = EventReader::new();
+
+        world.send_event(Collision(1));
+        assert_eq!(read(&world, &mut physics), [1]);
+
+        world.update_events();
+        world
+            .event_writer()
+            .send_batch([Collision(2), Collision(3)]);
+
+        // Nothing is read twice, and events from the previous frame are not lost
+        assert_eq!(read(&world, &mut physics), [2, 3]);
+        assert_eq!(read(&world, &mut physics), Vec::<u32>::new());
+        assert_eq!(read(&world, &mut audio), [1, 2, 3]);
+    }
+
+    #[test]
+    fn slow_readers_miss_old_events() {
+        let mut world = World::new();
+        let mut reader = EventReader::new();
+        assert_eq!(read(&world, &mut reader), Vec::<u32>::new());
+
+        world.send_event(Collision(1));
+        world.update_events();
+        world.send_event(Collision(2));
+        world.update_events();
+        world.send_event(Collision(3));
+
+        let events = world.resource::<Events<Collision>>().unwrap();
+        assert_eq!(reader.missed(events), 1);
+        assert_eq!(read(&world, &mut reader), [2, 3]);
+
+        world.update_events();
+        world.update_events();
+        let events = world.resource::<Events<Collision>>().unwrap();
+        assert_eq!(reader.missed(events), 0);
+        assert!(reader.is_empty(events));
+    }
+
+    #[test]
+    fn readers_ahead_of_a_queue_see_no_events() {
+        let mut events = Events::new();
+        events.send(Collision(1));
+        events.send(Collision(2));
+        let mut reader = EventReader::new();
+        assert_eq!(reader.read(&events).count(), 2);
+
+        // A replaced queue starts counting from zero again
+        let mut events = Events::new();
+        events.send(Collision(3));
+        assert_eq!(reader.len(&events), 0);
+        assert!(reader.is_empty(&events));
+        assert_eq!(reader.read(&events).count(), 0);
+    }
+}
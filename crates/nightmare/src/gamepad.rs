@@ -0,0 +1,546 @@
+use crate::{input::ButtonInput, Instant};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, VecDeque},
+    rc::Rc,
+};
+
+/// Identifies a gamepad for as long as the backend can recognize it,
+/// so a pad that reconnects keeps its id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GamepadId(pub usize);
+
+/// Buttons named by their position on the pad
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    /// The left trigger pulled past its actuation point,
+    /// see `GamepadAxis::LeftTrigger` for how far
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// Sticks range from -1 to 1 with up as positive y, and triggers from 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadEvent {
+    Connected {
+        id: GamepadId,
+        name: String,
+    },
+    Disconnected {
+        id: GamepadId,
+    },
+    ButtonPressed {
+        id: GamepadId,
+        button: GamepadButton,
+    },
+    ButtonReleased {
+        id: GamepadId,
+        button: GamepadButton,
+    },
+    AxisChanged {
+        id: GamepadId,
+        axis: GamepadAxis,
+        value: f32,
+    },
+}
+
+impl GamepadEvent {
+    pub fn id(&self) -> GamepadId {
+        match *self {
+            Self::Connected { id, .. }
+            | Self::Disconnected { id }
+            | Self::ButtonPressed { id, .. }
+            | Self::ButtonReleased { id, .. }
+            | Self::AxisChanged { id, .. } => id,
+        }
+    }
+}
+
+/// A source of gamepad events, such as an OS gamepad library
+pub trait GamepadBackend {
+    /// The next pending event, or `None` once every event has been received
+    fn next_event(&mut self) -> Option<GamepadEvent>;
+}
+
+/// A gamepad backend driven by hand, for tests and for machines without controllers.
+///
+/// Clones share the same queue, so one clone can be given to `Gamepads`
+/// while another sends the events.
+#[derive(Default, Debug, Clone)]
+pub struct FakeGamepadBackend {
+    queue: Rc<RefCell<VecDeque<GamepadEvent>>>,
+}
+
+impl FakeGamepadBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&self, event: GamepadEvent) {
+        self.queue.borrow_mut().push_back(event);
+    }
+
+    pub fn connect(&self, id: GamepadId, name: impl Into<String>) {
+        self.send(GamepadEvent::Connected {
+            id,
+            name: name.into(),
+        });
+    }
+
+    pub fn disconnect(&self, id: GamepadId) {
+        self.send(GamepadEvent::Disconnected { id });
+    }
+
+    pub fn press(&self, id: GamepadId, button: GamepadButton) {
+        self.send(GamepadEvent::ButtonPressed { id, button });
+    }
+
+    pub fn release(&self, id: GamepadId, button: GamepadButton) {
+        self.send(GamepadEvent::ButtonReleased { id, button });
+    }
+
+    pub fn set_axis(&self, id: GamepadId, axis: GamepadAxis, value: f32) {
+        self.send(GamepadEvent::AxisChanged { id, axis, value });
+    }
+}
+
+impl GamepadBackend for FakeGamepadBackend {
+    fn next_event(&mut self) -> Option<GamepadEvent> {
+        self.queue.borrow_mut().pop_front()
+    }
+}
+
+/// Ignores small movements of a worn stick or trigger near rest,
+/// and treats anything past `outer` as full deflection
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeadZone {
+    pub inner: f32,
+    pub outer: f32,
+}
+
+impl Default for DeadZone {
+    fn default() -> Self {
+        Self {
+            inner: 0.15,
+            outer: 0.95,
+        }
+    }
+}
+
+impl DeadZone {
+    /// Rescales a single axis so the live range maps onto 0 to 1
+    pub fn apply(&self, value: f32) -> f32 {
+        self.rescale(value.abs()).copysign(value)
+    }
+
+    /// Applies the dead zone to a stick's distance from the center,
+    /// keeping its direction
+    pub fn apply_radial(&self, stick: nalgebra_glm::Vec2) -> nalgebra_glm::Vec2 {
+        let length = stick.norm();
+        if length <= self.inner {
+            return nalgebra_glm::Vec2::zeros();
+        }
+        stick * (self.rescale(length) / length)
+    }
+
+    fn rescale(&self, magnitude: f32) -> f32 {
+        if magnitude <= self.inner {
+            return 0.0;
+        }
+        let range = (self.outer - self.inner).max(f32::EPSILON);
+        ((magnitude - self.inner) / range).min(1.0)
+    }
+}
+
+/// A connected gamepad
+#[derive(Debug, Clone)]
+pub struct Gamepad {
+    pub name: String,
+    /// Starts as `Gamepads::dead_zone` when the pad connects
+    pub dead_zone: DeadZone,
+    pub buttons: ButtonInput<GamepadButton>,
+    axes: HashMap<GamepadAxis, f32>,
+}
+
+impl Gamepad {
+    pub fn new(name: impl Into<String>, dead_zone: DeadZone) -> Self {
+        Self {
+            name: name.into(),
+            dead_zone,
+            buttons: ButtonInput::default(),
+            axes: HashMap::new(),
+        }
+    }
+
+    pub fn pressed(&self, button: GamepadButton) -> bool {
+        self.buttons.pressed(button)
+    }
+
+    pub fn just_pressed(&self, button: GamepadButton) -> bool {
+        self.buttons.just_pressed(button)
+    }
+
+    pub fn just_released(&self, button: GamepadButton) -> bool {
+        self.buttons.just_released(button)
+    }
+
+    /// The axis as reported by the backend
+    pub fn axis_raw(&self, axis: GamepadAxis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or_default()
+    }
+
+    /// The axis with the dead zone applied on its own.
+    /// Prefer `left_stick` and `right_stick` for reading both axes of a stick.
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.dead_zone.apply(self.axis_raw(axis))
+    }
+
+    pub fn left_stick(&self) -> nalgebra_glm::Vec2 {
+        self.stick(GamepadAxis::LeftStickX, GamepadAxis::LeftStickY)
+    }
+
+    pub fn right_stick(&self) -> nalgebra_glm::Vec2 {
+        self.stick(GamepadAxis::RightStickX, GamepadAxis::RightStickY)
+    }
+
+    pub fn left_trigger(&self) -> f32 {
+        self.axis(GamepadAxis::LeftTrigger)
+    }
+
+    pub fn right_trigger(&self) -> f32 {
+        self.axis(GamepadAxis::RightTrigger)
+    }
+
+    fn stick(&self, x: GamepadAxis, y: GamepadAxis) -> nalgebra_glm::Vec2 {
+        self.dead_zone
+            .apply_radial(nalgebra_glm::vec2(self.axis_raw(x), self.axis_raw(y)))
+    }
+}
+
+/// Every connected gamepad, updated from a backend once per frame
+#[derive(Default)]
+pub struct Gamepads {
+    /// The dead zone given to newly connected pads
+    pub dead_zone: DeadZone,
+    backend: Option<Box<dyn GamepadBackend>>,
+    gamepads: BTreeMap<GamepadId, Gamepad>,
+    events: Vec<GamepadEvent>,
+}
+
+impl Gamepads {
+    pub fn set_backend(&mut self, backend: impl GamepadBackend + 'static) {
+        self.backend = Some(Box::new(backend));
+    }
+
+    pub fn has_backend(&self) -> bool {
+        self.backend.is_some()
+    }
+
+    /// Starts a new frame at `time` and receives every pending backend event
+    pub fn update(&mut self, time: Instant) {
+        self.events.clear();
+        for gamepad in self.gamepads.values_mut() {
+            gamepad.buttons.new_frame(time);
+        }
+        while let Some(event) = self
+            .backend
+            .as_mut()
+            .and_then(|backend| backend.next_event())
+        {
+            self.receive_event(event, time);
+        }
+    }
+
+    /// Applies a single event, which is also listed in `events` until the next update
+    pub fn receive_event(&mut self, event: GamepadEvent, time: Instant) {
+        match &event {
+            GamepadEvent::Connected { id, name } => {
+                self.gamepads
+                    .insert(*id, Gamepad::new(name.clone(), self.dead_zone));
+            }
+            GamepadEvent::Disconnected { id } => {
+                self.gamepads.remove(id);
+            }
+            GamepadEvent::ButtonPressed { id, button } => {
+                if let Some(gamepad) = self.gamepads.get_mut(id) {
+                    gamepad.buttons.press(*button, time);
+                }
+            }
+            GamepadEvent::ButtonReleased { id, button } => {
+                if let Some(gamepad) = self.gamepads.get_mut(id) {
+                    gamepad.buttons.release(*button);
+                }
+            }
+            GamepadEvent::AxisChanged { id, axis, value } => {
+                if let Some(gamepad) = self.gamepads.get_mut(id) {
+                    gamepad.axes.insert(*axis, *value);
+                }
+            }
+        }
+        self.events.push(event);
+    }
+
+    /// The events received this frame, including connections and disconnections
+    pub fn events(&self) -> &[GamepadEvent] {
+        &self.events
+    }
+
+    pub fn get(&self, id: GamepadId) -> Option<&Gamepad> {
+        self.gamepads.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: GamepadId) -> Option<&mut Gamepad> {
+        self.gamepads.get_mut(&id)
+    }
+
+    /// The connected pad with the lowest id, for single player games
+    pub fn first(&self) -> Option<&Gamepad> {
+        self.gamepads.values().next()
+    }
+
+    /// Every connected pad, ordered by id
+    pub fn iter(&self) -> impl Iterator<Item = (GamepadId, &Gamepad)> {
+        self.gamepads.iter().map(|(id, gamepad)| (*id, gamepad))
+    }
+
+    pub fn len(&self) -> usize {
+        self.gamepads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gamepads.is_empty()
+    }
+}
+
+/// Reads gamepads through gilrs, which needs libudev on Linux
+#[cfg(feature = "gilrs")]
+pub struct GilrsBackend {
+    gilrs: gilrs::Gilrs,
+    pending: VecDeque<GamepadEvent>,
+}
+
+#[cfg(feature = "gilrs")]
+impl GilrsBackend {
+    /// Connects to the OS, reporting already connected pads as new connections
+    pub fn new() -> crate::genvec::Result<Self> {
+        let gilrs = gilrs::Gilrs::new()?;
+        let pending = gilrs
+            .gamepads()
+            .map(|(id, gamepad)| GamepadEvent::Connected {
+                id: GamepadId(id.into()),
+                name: gamepad.name().to_string(),
+            })
+            .collect();
+        Ok(Self { gilrs, pending })
+    }
+
+    fn button(button: gilrs::Button) -> Option<GamepadButton> {
+        Some(match button {
+            gilrs::Button::South => GamepadButton::South,
+            gilrs::Button::East => GamepadButton::East,
+            gilrs::Button::North => GamepadButton::North,
+            gilrs::Button::West => GamepadButton::West,
+            gilrs::Button::LeftTrigger => GamepadButton::LeftBumper,
+            gilrs::Button::RightTrigger => GamepadButton::RightBumper,
+            gilrs::Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+            gilrs::Button::RightTrigger2 => GamepadButton::RightTrigger,
+            gilrs::Button::Select => GamepadButton::Select,
+            gilrs::Button::Start => GamepadButton::Start,
+            gilrs::Button::Mode => GamepadButton::Mode,
+            gilrs::Button::LeftThumb => GamepadButton::LeftStick,
+            gilrs::Button::RightThumb => GamepadButton::RightStick,
+            gilrs::Button::DPadUp => GamepadButton::DPadUp,
+            gilrs::Button::DPadDown => GamepadButton::DPadDown,
+            gilrs::Button::DPadLeft => GamepadButton::DPadLeft,
+            gilrs::Button::DPadRight => GamepadButton::DPadRight,
+            _ => return None,
+        })
+    }
+
+    fn axis(axis: gilrs::Axis) -> Option<GamepadAxis> {
+        Some(match axis {
+            gilrs::Axis::LeftStickX => GamepadAxis::LeftStickX,
+            gilrs::Axis::LeftStickY => GamepadAxis::LeftStickY,
+            gilrs::Axis::RightStickX => GamepadAxis::RightStickX,
+            gilrs::Axis::RightStickY => GamepadAxis::RightStickY,
+            _ => return None,
+        })
+    }
+
+    fn convert(&self, event: gilrs::Event) -> Option<GamepadEvent> {
+        let id = GamepadId(event.id.into());
+        match event.event {
+            gilrs::EventType::Connected => Some(GamepadEvent::Connected {
+                id,
+                name: self.gilrs.gamepad(event.id).name().to_string(),
+            }),
+            gilrs::EventType::Disconnected => Some(GamepadEvent::Disconnected { id }),
+            gilrs::EventType::ButtonPressed(button, _) => {
+                Self::button(button).map(|button| GamepadEvent::ButtonPressed { id, button })
+            }
+            gilrs::EventType::ButtonReleased(button, _) => {
+                Self::button(button).map(|button| GamepadEvent::ButtonReleased { id, button })
+            }
+            // Analog triggers are reported as button values
+            gilrs::EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => {
+                Some(GamepadEvent::AxisChanged {
+                    id,
+                    axis: GamepadAxis::LeftTrigger,
+                    value,
+                })
+            }
+            gilrs::EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => {
+                Some(GamepadEvent::AxisChanged {
+                    id,
+                    axis: GamepadAxis::RightTrigger,
+                    value,
+                })
+            }
+            gilrs::EventType::AxisChanged(axis, value, _) => {
+                Self::axis(axis).map(|axis| GamepadEvent::AxisChanged { id, axis, value })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "gilrs")]
+impl GamepadBackend for GilrsBackend {
+    fn next_event(&mut self) -> Option<GamepadEvent> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        while let Some(event) = self.gilrs.next_event() {
+            if let Some(event) = self.convert(event) {
+                return Some(event);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Duration;
+
+    fn gamepads() -> (Gamepads, FakeGamepadBackend) {
+        let backend = FakeGamepadBackend::new();
+        let mut gamepads = Gamepads::default();
+        gamepads.set_backend(backend.clone());
+        (gamepads, backend)
+    }
+
+    #[test]
+    fn pads_connect_and_disconnect() {
+        let (mut gamepads, backend) = gamepads();
+        let now = Instant::now();
+        backend.connect(GamepadId(3), "Pad A");
+        backend.connect(GamepadId(1), "Pad B");
+        gamepads.update(now);
+
+        assert_eq!(gamepads.len(), 2);
+        assert_eq!(gamepads.first().unwrap().name, "Pad B");
+        assert_eq!(gamepads.events().len(), 2);
+
+        backend.disconnect(GamepadId(1));
+        backend.press(GamepadId(1), GamepadButton::South);
+        gamepads.update(now);
+        assert!(gamepads.get(GamepadId(1)).is_none());
+        assert_eq!(
+            gamepads.events()[0],
+            GamepadEvent::Disconnected { id: GamepadId(1) }
+        );
+
+        // A pad that comes back keeps its id
+        backend.connect(GamepadId(1), "Pad B");
+        gamepads.update(now);
+        assert_eq!(
+            gamepads.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            [GamepadId(1), GamepadId(3)]
+        );
+        gamepads.update(now);
+        assert!(gamepads.events().is_empty());
+    }
+
+    #[test]
+    fn buttons_have_per_frame_transitions() {
+        let (mut gamepads, backend) = gamepads();
+        let start = Instant::now();
+        let id = GamepadId(0);
+        backend.connect(id, "Pad");
+        backend.press(id, GamepadButton::South);
+        gamepads.update(start);
+
+        let gamepad = gamepads.get(id).unwrap();
+        assert!(gamepad.pressed(GamepadButton::South));
+        assert!(gamepad.just_pressed(GamepadButton::South));
+
+        gamepads.update(start + Duration::from_millis(250));
+        let gamepad = gamepads.get(id).unwrap();
+        assert!(!gamepad.just_pressed(GamepadButton::South));
+        assert_eq!(
+            gamepad.buttons.press_duration(GamepadButton::South),
+            Some(Duration::from_millis(250))
+        );
+
+        backend.release(id, GamepadButton::South);
+        gamepads.update(start + Duration::from_millis(300));
+        assert!(gamepads
+            .get(id)
+            .unwrap()
+            .just_released(GamepadButton::South));
+    }
+
+    #[test]
+    fn dead_zones() {
+        let (mut gamepads, backend) = gamepads();
+        gamepads.dead_zone = DeadZone {
+            inner: 0.2,
+            outer: 0.8,
+        };
+        let id = GamepadId(0);
+        backend.connect(id, "Pad");
+        backend.set_axis(id, GamepadAxis::LeftStickX, 0.1);
+        backend.set_axis(id, GamepadAxis::LeftStickY, 0.1);
+        backend.set_axis(id, GamepadAxis::RightStickX, -0.5);
+        backend.set_axis(id, GamepadAxis::LeftTrigger, 0.9);
+        gamepads.update(Instant::now());
+
+        let gamepad = gamepads.get(id).unwrap();
+        assert_eq!(gamepad.left_stick(), nalgebra_glm::Vec2::zeros());
+        assert_eq!(gamepad.axis_raw(GamepadAxis::LeftStickX), 0.1);
+        assert!((gamepad.right_stick() - nalgebra_glm::vec2(-0.5, 0.0)).norm() < 1e-6);
+        assert_eq!(gamepad.left_trigger(), 1.0);
+        assert_eq!(gamepad.right_trigger(), 0.0);
+    }
+}
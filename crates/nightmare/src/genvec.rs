@@ -1,7 +1,14 @@
 #![allow(dead_code)]
 
-use self::error::GenerationError;
-use std::ops::{Deref, DerefMut};
+use self::error::{GenerationError, HandleNotFoundError};
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+};
 
 pub mod error {
     use super::*;
@@ -38,189 +45,1271 @@ pub type Result<T, E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
 pub type SlotVec<T> = Vec<Option<Slot<T>>>;
 
 #[derive(Default, Debug, PartialEq, Eq, Copy, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Handle {
-    index: usize,
-    generation: usize,
+    index: u32,
+    generation: u32,
 }
 
 impl Handle {
-    pub const fn index(&self) -> &usize {
+    /// A handle that is never allocated,
+    /// handed out by reservations once every index is in use
+    pub const INVALID: Self = Self {
+        index: u32::MAX,
+        generation: u32::MAX,
+    };
+
+    pub const fn index(&self) -> &u32 {
         &self.index
     }
 
-    pub const fn generation(&self) -> &usize {
+    pub const fn generation(&self) -> &u32 {
         &self.generation
     }
+
+    /// Packs the handle into 64 bits, with the generation in the high half
+    pub const fn to_bits(&self) -> u64 {
+        (self.generation as u64) << 32 | self.index as u64
+    }
+
+    pub const fn from_bits(bits: u64) -> Self {
+        Self {
+            index: bits as u32,
+            generation: (bits >> 32) as u32,
+        }
+    }
+
+    fn from_slot(slot: usize, generation: u32) -> Self {
+        Self {
+            index: u32::try_from(slot).expect("Handle index exceeds u32::MAX!"),
+            generation,
+        }
+    }
+
+    /// The index as a position in a slot vec
+    const fn slot(&self) -> usize {
+        self.index as usize
+    }
+}
+
+/// A `Handle` that can only be used with storages of `T`
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent, bound = "")
+)]
+pub struct TypedHandle<T> {
+    handle: Handle,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> TypedHandle<T> {
+    pub const fn new(handle: Handle) -> Self {
+        Self {
+            handle,
+            _marker: PhantomData,
+        }
+    }
+
+    pub const fn handle(&self) -> Handle {
+        self.handle
+    }
+}
+
+impl<T> Clone for TypedHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
 }
 
+impl<T> Copy for TypedHandle<T> {}
+
+impl<T> PartialEq for TypedHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+}
+
+impl<T> Eq for TypedHandle<T> {}
+
+impl<T> std::hash::Hash for TypedHandle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.handle.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for TypedHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple(&format!("TypedHandle<{}>", std::any::type_name::<T>()))
+            .field(&self.handle)
+            .finish()
+    }
+}
+
+impl<T> Default for TypedHandle<T> {
+    fn default() -> Self {
+        Self::new(Handle::default())
+    }
+}
+
+impl<T> From<Handle> for TypedHandle<T> {
+    fn from(handle: Handle) -> Self {
+        Self::new(handle)
+    }
+}
+
+impl<T> From<TypedHandle<T>> for Handle {
+    fn from(typed_handle: TypedHandle<T>) -> Self {
+        typed_handle.handle
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GenerationalVec<T> {
     elements: SlotVec<T>,
+    /// The latest generation inserted into each slot, kept once the slot is vacant
+    /// so stale handles are still rejected after a removal or `clear`
+    #[cfg_attr(feature = "serde", serde(default))]
+    generations: Vec<u32>,
+}
+
+impl<T> GenerationalVec<T> {
+    pub fn new(elements: SlotVec<T>) -> Self {
+        Self {
+            elements,
+            generations: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, handle: Handle, value: T) -> Result<()> {
+        if handle == Handle::INVALID {
+            return Err(Box::new(HandleNotFoundError { handle }));
+        }
+        while self.elements.len() <= handle.slot() {
+            self.elements.push(None);
+        }
+
+        let occupied_generation = match self.elements.get(handle.slot()) {
+            Some(Some(entry)) => entry.generation,
+            _ => 0,
+        };
+        let previous_generation = self
+            .generations
+            .get(handle.slot())
+            .copied()
+            .unwrap_or_default()
+            .max(occupied_generation);
+
+        if previous_generation > handle.generation {
+            return Err(Box::new(GenerationError { handle }));
+        }
+
+        if self.generations.len() <= handle.slot() {
+            self.generations.resize(handle.slot() + 1, 0);
+        }
+        self.generations[handle.slot()] = handle.generation;
+
+        self.elements[handle.slot()] = Some(Slot {
+            value,
+            generation: handle.generation,
+            ticks: None,
+        });
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, handle: Handle) {
+        if let Some(e) = self.elements.get_mut(handle.slot()) {
+            *e = None;
+        }
+    }
+
+    /// Removes and returns the element if the handle's generation matches
+    pub fn take(&mut self, handle: Handle) -> Option<T> {
+        self.get(handle)?;
+        self.elements[handle.slot()].take().map(Slot::into_value)
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        if handle.slot() >= self.elements.len() {
+            return None;
+        }
+        self.elements[handle.slot()]
+            .as_ref()
+            .filter(|c| c.generation == handle.generation)
+            .map(|entry| &entry.value)
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        if handle.slot() >= self.elements.len() {
+            return None;
+        }
+        self.elements[handle.slot()]
+            .as_mut()
+            .filter(|c| c.generation == handle.generation)
+            .map(|entry| &mut entry.value)
+    }
+
+    /// The change ticks of an element, if any were recorded
+    pub fn ticks(&self, handle: Handle) -> Option<ComponentTicks> {
+        self.slot(handle)?.ticks
+    }
+
+    /// Starts or updates change tracking for an element
+    pub fn set_ticks(&mut self, handle: Handle, ticks: ComponentTicks) {
+        if let Some(slot) = self.slot_mut(handle) {
+            slot.ticks = Some(ticks);
+        }
+    }
+
+    pub fn get_mut_with_ticks(
+        &mut self,
+        handle: Handle,
+    ) -> Option<(&mut T, Option<&mut ComponentTicks>)> {
+        let slot = self.slot_mut(handle)?;
+        Some((&mut slot.value, slot.ticks.as_mut()))
+    }
+
+    fn slot(&self, handle: Handle) -> Option<&Slot<T>> {
+        self.elements
+            .get(handle.slot())?
+            .as_ref()
+            .filter(|slot| slot.generation == handle.generation)
+    }
+
+    fn slot_mut(&mut self, handle: Handle) -> Option<&mut Slot<T>> {
+        self.elements
+            .get_mut(handle.slot())?
+            .as_mut()
+            .filter(|slot| slot.generation == handle.generation)
+    }
+
+    /// The number of occupied slots
+    pub fn len(&self) -> usize {
+        self.elements.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.iter().all(Option::is_none)
+    }
+
+    /// Removes every element, keeping each slot's generation
+    pub fn clear(&mut self) {
+        self.elements.iter_mut().for_each(|element| *element = None);
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            elements: self.elements.iter().enumerate(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            elements: self.elements.iter_mut().enumerate(),
+        }
+    }
+
+    /// The handles of every occupied slot
+    pub fn handles(&self) -> impl Iterator<Item = Handle> + '_ {
+        self.iter().map(|(handle, _)| handle)
+    }
+
+    /// Removes and yields every element, leaving the vec empty
+    pub fn drain(&mut self) -> IntoIter<T> {
+        IntoIter {
+            elements: std::mem::take(&mut self.elements).into_iter().enumerate(),
+        }
+    }
+
+    /// Removes every element the predicate returns `false` for
+    pub fn retain(&mut self, mut predicate: impl FnMut(Handle, &mut T) -> bool) {
+        for (index, element) in self.elements.iter_mut().enumerate() {
+            if let Some(slot) = element {
+                let handle = Handle::from_slot(index, slot.generation);
+                if !predicate(handle, &mut slot.value) {
+                    *element = None;
+                }
+            }
+        }
+    }
+}
+
+/// The interface shared by the storages a component type can be kept in
+///
+/// # Safety
+///
+/// Queries dereference the pointers returned by `get_ptr` and `get_mut_ptr`
+/// while holding references to other elements, so implementors must ensure:
+///
+/// - the pointers are valid for the element stored under the handle,
+///   and stay valid until the storage is next modified through `&mut self`
+///   in a method other than `get_mut_ptr`
+/// - the pointers for different handles never overlap
+/// - neither method creates a reference to any other element
+/// - `handles` yields each handle at most once
+pub unsafe trait Storage<T> {
+    fn insert(&mut self, handle: Handle, value: T) -> Result<()>;
+    fn remove(&mut self, handle: Handle);
+    fn take(&mut self, handle: Handle) -> Option<T>;
+    fn get(&self, handle: Handle) -> Option<&T>;
+    fn get_mut(&mut self, handle: Handle) -> Option<&mut T>;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool;
+    fn clear(&mut self);
+
+    /// The handles of every stored element, in iteration order
+    fn handles(&self) -> Box<dyn Iterator<Item = Handle> + '_>;
+
+    /// The change ticks of an element, for storages that record them
+    fn ticks(&self, _handle: Handle) -> Option<ComponentTicks> {
+        None
+    }
+
+    /// Records change ticks for an element, for storages that support it
+    fn set_ticks(&mut self, _handle: Handle, _ticks: ComponentTicks) {}
+
+    /// Keeps every recorded tick within `MAX_CHANGE_AGE` of `this_run`
+    fn clamp_ticks(&mut self, this_run: u32) {
+        let handles = self.handles().collect::<Vec<_>>();
+        for handle in handles {
+            if let Some(mut ticks) = self.ticks(handle) {
+                ticks.clamp(this_run);
+                self.set_ticks(handle, ticks);
+            }
+        }
+    }
+
+    /// An element along with its change ticks, if they are recorded
+    fn get_mut_with_ticks(
+        &mut self,
+        handle: Handle,
+    ) -> Option<(&mut T, Option<&mut ComponentTicks>)> {
+        self.get_mut(handle).map(|value| (value, None))
+    }
+
+    /// Pointers to an element and its change ticks.
+    ///
+    /// Unlike `get`, this must not borrow any other element,
+    /// so references to other elements stay valid while it is called.
+    fn get_ptr(&self, handle: Handle) -> Option<(*const T, Option<*const ComponentTicks>)>;
+
+    /// Mutable pointers to an element and its change ticks.
+    ///
+    /// Unlike `get_mut`, this must not borrow any other element,
+    /// so references to other elements stay valid while it is called.
+    fn get_mut_ptr(&mut self, handle: Handle) -> Option<(*mut T, Option<*mut ComponentTicks>)>;
+}
+
+// Safety: `get_ptr` and `get_mut_ptr` only borrow the slot of the requested handle
+unsafe impl<T> Storage<T> for GenerationalVec<T> {
+    fn insert(&mut self, handle: Handle, value: T) -> Result<()> {
+        GenerationalVec::insert(self, handle, value)
+    }
+
+    fn remove(&mut self, handle: Handle) {
+        GenerationalVec::remove(self, handle)
+    }
+
+    fn take(&mut self, handle: Handle) -> Option<T> {
+        GenerationalVec::take(self, handle)
+    }
+
+    fn get(&self, handle: Handle) -> Option<&T> {
+        GenerationalVec::get(self, handle)
+    }
+
+    fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        GenerationalVec::get_mut(self, handle)
+    }
+
+    fn len(&self) -> usize {
+        GenerationalVec::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        GenerationalVec::is_empty(self)
+    }
+
+    fn clear(&mut self) {
+        GenerationalVec::clear(self)
+    }
+
+    fn handles(&self) -> Box<dyn Iterator<Item = Handle> + '_> {
+        Box::new(GenerationalVec::handles(self))
+    }
+
+    fn ticks(&self, handle: Handle) -> Option<ComponentTicks> {
+        GenerationalVec::ticks(self, handle)
+    }
+
+    fn set_ticks(&mut self, handle: Handle, ticks: ComponentTicks) {
+        GenerationalVec::set_ticks(self, handle, ticks)
+    }
+
+    fn get_mut_with_ticks(
+        &mut self,
+        handle: Handle,
+    ) -> Option<(&mut T, Option<&mut ComponentTicks>)> {
+        GenerationalVec::get_mut_with_ticks(self, handle)
+    }
+
+    fn get_ptr(&self, handle: Handle) -> Option<(*const T, Option<*const ComponentTicks>)> {
+        if handle.slot() >= self.elements.len() {
+            return None;
+        }
+        // Safety: the index is in bounds, and only this element is borrowed
+        let element = unsafe { &*self.elements.as_ptr().add(handle.slot()) };
+        let slot = element
+            .as_ref()
+            .filter(|slot| slot.generation == handle.generation)?;
+        Some((
+            &slot.value as *const T,
+            slot.ticks.as_ref().map(|ticks| ticks as *const _),
+        ))
+    }
+
+    fn get_mut_ptr(&mut self, handle: Handle) -> Option<(*mut T, Option<*mut ComponentTicks>)> {
+        if handle.slot() >= self.elements.len() {
+            return None;
+        }
+        // Safety: the index is in bounds, and only this element is borrowed
+        let element = unsafe { &mut *self.elements.as_mut_ptr().add(handle.slot()) };
+        let slot = element
+            .as_mut()
+            .filter(|slot| slot.generation == handle.generation)?;
+        Some((
+            &mut slot.value as *mut T,
+            slot.ticks.as_mut().map(|ticks| ticks as *mut _),
+        ))
+    }
+}
+
+pub struct Iter<'a, T> {
+    elements: std::iter::Enumerate<std::slice::Iter<'a, Option<Slot<T>>>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (Handle, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.elements.find_map(|(index, element)| {
+            element.as_ref().map(|slot| {
+                let handle = Handle::from_slot(index, slot.generation);
+                (handle, &slot.value)
+            })
+        })
+    }
+}
+
+pub struct IterMut<'a, T> {
+    elements: std::iter::Enumerate<std::slice::IterMut<'a, Option<Slot<T>>>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (Handle, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.elements.find_map(|(index, element)| {
+            element.as_mut().map(|slot| {
+                let handle = Handle::from_slot(index, slot.generation);
+                (handle, &mut slot.value)
+            })
+        })
+    }
+}
+
+pub struct IntoIter<T> {
+    elements: std::iter::Enumerate<std::vec::IntoIter<Option<Slot<T>>>>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = (Handle, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.elements.find_map(|(index, element)| {
+            element.map(|slot| {
+                let handle = Handle::from_slot(index, slot.generation);
+                (handle, slot.value)
+            })
+        })
+    }
+}
+
+impl<T> IntoIterator for GenerationalVec<T> {
+    type Item = (Handle, T);
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            elements: self.elements.into_iter().enumerate(),
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a GenerationalVec<T> {
+    type Item = (Handle, &'a T);
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut GenerationalVec<T> {
+    type Item = (Handle, &'a mut T);
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T> Deref for GenerationalVec<T> {
+    type Target = SlotVec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.elements
+    }
+}
+
+impl<T> DerefMut for GenerationalVec<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.elements
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Slot<T> {
+    value: T,
+    generation: u32,
+    /// Only recorded while the slot lives in a world, so never serialized
+    #[cfg_attr(feature = "serde", serde(skip))]
+    ticks: Option<ComponentTicks>,
+}
+
+impl<T> Slot<T> {
+    pub const fn new(value: T, generation: u32) -> Self {
+        Self {
+            value,
+            generation,
+            ticks: None,
+        }
+    }
+
+    pub const fn generation(&self) -> &u32 {
+        &self.generation
+    }
+
+    pub const fn ticks(&self) -> Option<ComponentTicks> {
+        self.ticks
+    }
+
+    pub const fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn value_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for Slot<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Slot<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+/// The world change ticks at which a component was added and last changed
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentTicks {
+    pub added: u32,
+    pub changed: u32,
+}
+
+impl ComponentTicks {
+    pub const fn new(tick: u32) -> Self {
+        Self {
+            added: tick,
+            changed: tick,
+        }
+    }
+
+    /// Whether the component was added at or after `last_run`,
+    /// as seen from `this_run`
+    pub fn is_added(&self, last_run: u32, this_run: u32) -> bool {
+        is_newer(self.added, last_run, this_run)
+    }
+
+    /// Whether the component was added or changed at or after `last_run`,
+    /// as seen from `this_run`
+    pub fn is_changed(&self, last_run: u32, this_run: u32) -> bool {
+        is_newer(self.changed, last_run, this_run)
+    }
+
+    /// Keeps both ticks within `MAX_CHANGE_AGE` of `this_run`
+    pub fn clamp(&mut self, this_run: u32) {
+        clamp_tick(&mut self.added, this_run);
+        clamp_tick(&mut self.changed, this_run);
+    }
+}
+
+/// How many ticks may pass between passes that clamp old ticks
+pub const CHECK_TICK_THRESHOLD: u32 = 518_400_000;
+
+/// The oldest a tick can get before it is clamped. Ticks are clamped at least every
+/// `CHECK_TICK_THRESHOLD` ticks, so none can wrap around and look new again.
+pub const MAX_CHANGE_AGE: u32 = u32::MAX - (2 * CHECK_TICK_THRESHOLD - 1);
+
+/// Compares ticks by their distance from `this_run` so wrapping around is harmless
+pub(crate) fn is_newer(tick: u32, last_run: u32, this_run: u32) -> bool {
+    this_run.wrapping_sub(tick) <= this_run.wrapping_sub(last_run)
 }
 
-impl<T> GenerationalVec<T> {
-    pub fn new(elements: SlotVec<T>) -> Self {
-        Self { elements }
+/// Moves a tick forward to `MAX_CHANGE_AGE` before `this_run` if it is older than that
+pub(crate) fn clamp_tick(tick: &mut u32, this_run: u32) {
+    if this_run.wrapping_sub(*tick) > MAX_CHANGE_AGE {
+        *tick = this_run.wrapping_sub(MAX_CHANGE_AGE);
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Allocation {
+    allocated: bool,
+    generation: u32,
+    /// Whether the slot was reserved and is waiting for the next flush
+    #[cfg_attr(feature = "serde", serde(skip))]
+    reserved: bool,
+}
+
+/// The free slots shared by an allocator and its reservers
+#[derive(Default)]
+struct FreeHandles {
+    /// Freed slots, each as the handle it is handed out as next
+    available: Vec<Handle>,
+    /// Freed slots handed out by `reserve` since the last flush
+    reserved: Vec<Handle>,
+    /// Reserved handles given back before the next flush
+    released: Vec<Handle>,
+}
+
+/// Hands out handles from any thread without access to the allocator.
+///
+/// Reservations reuse freed slots first and then take new ones.
+/// Reserved handles become allocated the next time the allocator
+/// they came from is flushed.
+#[derive(Default, Clone)]
+pub struct HandleReserver {
+    next_index: Arc<AtomicU32>,
+    free: Arc<Mutex<FreeHandles>>,
+}
+
+impl HandleReserver {
+    /// Reserves a freed slot or the next unused index,
+    /// or returns `Handle::INVALID` once every index has been handed out
+    pub fn reserve(&self) -> Handle {
+        let mut free = self.free();
+        if let Some(handle) = free.available.pop() {
+            free.reserved.push(handle);
+            return handle;
+        }
+        drop(free);
+        self.reserve_new()
+    }
+
+    /// Gives back a reserved handle, so the next flush frees it instead of keeping it alive
+    pub fn release(&self, handle: Handle) {
+        if handle != Handle::INVALID {
+            self.free().released.push(handle);
+        }
+    }
+
+    /// Reserves the next unused index, skipping the free slots
+    fn reserve_new(&self) -> Handle {
+        // The counter stops at u32::MAX, which is never used as an index
+        match self
+            .next_index
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |index| {
+                index.checked_add(1)
+            }) {
+            Ok(index) => Handle {
+                index,
+                generation: 0,
+            },
+            Err(_) => Handle::INVALID,
+        }
+    }
+
+    fn next_index(&self) -> usize {
+        self.next_index.load(Ordering::Acquire) as usize
+    }
+
+    fn free(&self) -> MutexGuard<'_, FreeHandles> {
+        // The free slots are never left half updated, so a poisoned lock is still usable
+        self.free.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(from = "HandleAllocatorSnapshot")
+)]
+pub struct HandleAllocator {
+    allocations: Vec<Allocation>,
+    /// Shares the free slots with reservations
+    reserver: HandleReserver,
+    /// Every slot below this index has been flushed or allocated directly
+    flushed: usize,
+}
+
+/// The serialized fields of a `HandleAllocator`,
+/// used to resume reservations after the loaded allocations
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct HandleAllocatorSnapshot<A = Vec<Allocation>> {
+    allocations: A,
+    available_handles: Vec<usize>,
+}
+
+/// Slots with pending reservations are saved as free,
+/// since the reserved handles are not carried over
+#[cfg(feature = "serde")]
+impl serde::Serialize for HandleAllocator {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let free = self.reserver.free();
+        let placeholders = self
+            .allocations
+            .iter()
+            .enumerate()
+            .filter(|(_, allocation)| allocation.reserved)
+            .map(|(index, _)| index);
+        let available_handles = free
+            .available
+            .iter()
+            .chain(free.reserved.iter())
+            .map(Handle::slot)
+            .chain(placeholders)
+            .collect();
+        HandleAllocatorSnapshot {
+            allocations: &self.allocations,
+            available_handles,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<HandleAllocatorSnapshot> for HandleAllocator {
+    fn from(snapshot: HandleAllocatorSnapshot) -> Self {
+        let available = snapshot
+            .available_handles
+            .into_iter()
+            .filter_map(|index| {
+                let generation = snapshot.allocations.get(index)?.generation.checked_add(1)?;
+                Some(Handle::from_slot(index, generation))
+            })
+            .collect();
+        Self {
+            reserver: HandleReserver {
+                next_index: Arc::new(AtomicU32::new(
+                    u32::try_from(snapshot.allocations.len()).unwrap_or(u32::MAX),
+                )),
+                free: Arc::new(Mutex::new(FreeHandles {
+                    available,
+                    ..Default::default()
+                })),
+            },
+            flushed: snapshot.allocations.len(),
+            allocations: snapshot.allocations,
+        }
+    }
+}
+
+impl HandleAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reuses a free slot with a bumped generation, or appends a new one.
+    /// Slots whose generation would wrap around are retired for good,
+    /// so a stale handle can never match a newer one.
+    /// Once every index is in use this returns `Handle::INVALID`, which is never allocated.
+    pub fn allocate(&mut self) -> Handle {
+        if let Some(handle) = self.reserver.free().available.pop() {
+            let allocation = &mut self.allocations[handle.slot()];
+            allocation.generation = handle.generation;
+            allocation.allocated = true;
+            return handle;
+        }
+
+        // Slots reserved by others in the meantime are left for `flush`
+        let handle = self.reserver.reserve_new();
+        if handle == Handle::INVALID {
+            return handle;
+        }
+        while self.allocations.len() < handle.slot() {
+            self.allocations.push(Allocation {
+                allocated: false,
+                generation: 0,
+                reserved: true,
+            });
+        }
+        self.allocations.push(Allocation {
+            allocated: true,
+            generation: 0,
+            reserved: false,
+        });
+        if self.flushed == handle.slot() {
+            self.flushed += 1;
+        }
+        handle
+    }
+
+    /// Reserves a handle without exclusive access,
+    /// which is safe to do from several threads at once.
+    /// The handle is not allocated until `flush` is called.
+    pub fn reserve(&self) -> Handle {
+        self.reserver.reserve()
+    }
+
+    /// A reserver that can outlive borrows of this allocator
+    pub fn reserver(&self) -> HandleReserver {
+        self.reserver.clone()
+    }
+
+    /// Allocates every handle reserved since the last flush
+    /// and frees the ones that were released again
+    pub fn flush(&mut self) {
+        for handle in std::mem::take(&mut self.reserver.free().reserved) {
+            let allocation = &mut self.allocations[handle.slot()];
+            allocation.generation = handle.generation;
+            allocation.allocated = true;
+        }
+
+        let next_index = self.reserver.next_index();
+        for allocation in self.allocations.iter_mut().skip(self.flushed) {
+            if allocation.reserved {
+                allocation.reserved = false;
+                allocation.allocated = true;
+            }
+        }
+        while self.allocations.len() < next_index {
+            self.allocations.push(Allocation {
+                allocated: true,
+                generation: 0,
+                reserved: false,
+            });
+        }
+        self.flushed = next_index;
+
+        let released = std::mem::take(&mut self.reserver.free().released);
+        for handle in released {
+            self.deallocate(&handle);
+        }
+    }
+
+    pub fn has_pending_reservations(&self) -> bool {
+        self.reserver.next_index() > self.flushed || !self.reserver.free().reserved.is_empty()
+    }
+
+    /// Frees a handle's slot for reuse with the next generation,
+    /// unless its generation is exhausted
+    pub fn deallocate(&mut self, handle: &Handle) {
+        if !self.is_allocated(handle) {
+            return;
+        }
+        self.allocations[handle.slot()].allocated = false;
+        if let Some(generation) = handle.generation.checked_add(1) {
+            self.reserver
+                .free()
+                .available
+                .push(Handle::from_slot(handle.slot(), generation));
+        }
+    }
+
+    pub fn is_allocated(&self, handle: &Handle) -> bool {
+        self.handle_exists(handle)
+            && self.allocations[handle.slot()].generation == handle.generation
+            && self.allocations[handle.slot()].allocated
+    }
+
+    pub fn handle_exists(&self, handle: &Handle) -> bool {
+        handle.slot() < self.allocations.len()
+    }
+
+    pub fn allocated_handles(&self) -> Vec<Handle> {
+        self.handles().collect()
+    }
+
+    /// Iterates over the currently allocated handles
+    pub fn handles(&self) -> impl Iterator<Item = Handle> + '_ {
+        self.allocations
+            .iter()
+            .enumerate()
+            .filter(|(_, allocation)| allocation.allocated)
+            .map(|(index, allocation)| Handle::from_slot(index, allocation.generation))
+    }
+
+    /// The number of currently allocated handles
+    pub fn len(&self) -> usize {
+        self.allocations
+            .iter()
+            .filter(|allocation| allocation.allocated)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Deallocates every handle.
+    /// Generations are kept so that old handles stay invalid.
+    pub fn clear(&mut self) {
+        for handle in self.allocated_handles() {
+            self.deallocate(&handle);
+        }
+    }
+}
+
+/// Stores values densely, with a sparse array mapping handle indices
+/// to positions in the dense array.
+///
+/// Iteration only touches occupied entries, which makes this a better fit
+/// than `GenerationalVec` for values that only a few handles have.
+/// Removal swaps the last element into the freed position,
+/// so iteration order is not stable.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "SparseSetSnapshot<T>")
+)]
+pub struct SparseSet<T> {
+    sparse: Vec<Option<usize>>,
+    handles: Vec<Handle>,
+    values: Vec<T>,
+    /// The change ticks of each value, in the same order as `values`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    ticks: Vec<Option<ComponentTicks>>,
+}
+
+/// The serialized fields of a `SparseSet`, which has no recorded ticks once loaded
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SparseSetSnapshot<T> {
+    sparse: Vec<Option<usize>>,
+    handles: Vec<Handle>,
+    values: Vec<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> From<SparseSetSnapshot<T>> for SparseSet<T> {
+    fn from(snapshot: SparseSetSnapshot<T>) -> Self {
+        Self {
+            ticks: vec![None; snapshot.values.len()],
+            sparse: snapshot.sparse,
+            handles: snapshot.handles,
+            values: snapshot.values,
+        }
+    }
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        Self {
+            sparse: Vec::new(),
+            handles: Vec::new(),
+            values: Vec::new(),
+            ticks: Vec::new(),
+        }
+    }
+}
+
+impl<T> SparseSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, handle: Handle, value: T) -> Result<()> {
+        if handle == Handle::INVALID {
+            return Err(Box::new(HandleNotFoundError { handle }));
+        }
+        if self.sparse.len() <= handle.slot() {
+            self.sparse.resize(handle.slot() + 1, None);
+        }
+
+        match self.sparse[handle.slot()] {
+            Some(position) => {
+                if self.handles[position].generation > handle.generation {
+                    return Err(Box::new(GenerationError { handle }));
+                }
+                self.handles[position] = handle;
+                self.values[position] = value;
+                self.ticks[position] = None;
+            }
+            None => {
+                self.sparse[handle.slot()] = Some(self.values.len());
+                self.handles.push(handle);
+                self.values.push(value);
+                self.ticks.push(None);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, handle: Handle) {
+        self.take(handle);
+    }
+
+    pub fn take(&mut self, handle: Handle) -> Option<T> {
+        let position = self.position(handle)?;
+        self.sparse[handle.slot()] = None;
+        self.handles.swap_remove(position);
+        let value = self.values.swap_remove(position);
+        self.ticks.swap_remove(position);
+        if let Some(moved) = self.handles.get(position) {
+            self.sparse[moved.slot()] = Some(position);
+        }
+        Some(value)
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        self.position(handle).map(|position| &self.values[position])
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        self.position(handle)
+            .map(|position| &mut self.values[position])
+    }
+
+    /// The change ticks of an element, if any were recorded
+    pub fn ticks(&self, handle: Handle) -> Option<ComponentTicks> {
+        self.ticks[self.position(handle)?]
+    }
+
+    /// Records the change ticks of an element, ignoring stale handles
+    pub fn set_ticks(&mut self, handle: Handle, ticks: ComponentTicks) {
+        if let Some(position) = self.position(handle) {
+            self.ticks[position] = Some(ticks);
+        }
+    }
+
+    /// An element along with its change ticks, if they were recorded
+    pub fn get_mut_with_ticks(
+        &mut self,
+        handle: Handle,
+    ) -> Option<(&mut T, Option<&mut ComponentTicks>)> {
+        let position = self.position(handle)?;
+        Some((&mut self.values[position], self.ticks[position].as_mut()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.sparse.clear();
+        self.handles.clear();
+        self.values.clear();
+        self.ticks.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Handle, &T)> {
+        self.handles.iter().copied().zip(self.values.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle, &mut T)> {
+        self.handles.iter().copied().zip(self.values.iter_mut())
     }
 
-    pub fn insert(&mut self, handle: Handle, value: T) -> Result<()> {
-        while self.elements.len() <= handle.index {
-            self.elements.push(None);
-        }
+    pub fn handles(&self) -> impl Iterator<Item = Handle> + '_ {
+        self.handles.iter().copied()
+    }
 
-        let previous_generation = match self.elements.get(handle.index) {
-            Some(Some(entry)) => entry.generation,
-            _ => 0,
-        };
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
 
-        if previous_generation > handle.generation {
-            return Err(Box::new(GenerationError { handle }));
-        }
+    fn position(&self, handle: Handle) -> Option<usize> {
+        self.sparse
+            .get(handle.slot())
+            .copied()
+            .flatten()
+            .filter(|position| self.handles[*position] == handle)
+    }
+}
 
-        self.elements[handle.index] = Some(Slot {
-            value,
-            generation: handle.generation,
-        });
+// Safety: `get_ptr` and `get_mut_ptr` only borrow the value and ticks at the handle's position
+unsafe impl<T> Storage<T> for SparseSet<T> {
+    fn insert(&mut self, handle: Handle, value: T) -> Result<()> {
+        SparseSet::insert(self, handle, value)
+    }
 
-        Ok(())
+    fn remove(&mut self, handle: Handle) {
+        SparseSet::remove(self, handle)
     }
 
-    pub fn remove(&mut self, handle: Handle) {
-        if let Some(e) = self.elements.get_mut(handle.index) {
-            *e = None;
-        }
+    fn take(&mut self, handle: Handle) -> Option<T> {
+        SparseSet::take(self, handle)
     }
 
-    pub fn get(&self, handle: Handle) -> Option<&T> {
-        if handle.index >= self.elements.len() {
-            return None;
-        }
-        self.elements[handle.index]
-            .as_ref()
-            .filter(|c| c.generation == handle.generation)
-            .map(|entry| &entry.value)
+    fn get(&self, handle: Handle) -> Option<&T> {
+        SparseSet::get(self, handle)
     }
 
-    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
-        if handle.index >= self.elements.len() {
-            return None;
-        }
-        self.elements[handle.index]
-            .as_mut()
-            .filter(|c| c.generation == handle.generation)
-            .map(|entry| &mut entry.value)
+    fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        SparseSet::get_mut(self, handle)
     }
-}
 
-impl<T> Deref for GenerationalVec<T> {
-    type Target = SlotVec<T>;
+    fn len(&self) -> usize {
+        SparseSet::len(self)
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.elements
+    fn is_empty(&self) -> bool {
+        SparseSet::is_empty(self)
     }
-}
 
-impl<T> DerefMut for GenerationalVec<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.elements
+    fn clear(&mut self) {
+        SparseSet::clear(self)
     }
-}
 
-pub struct Slot<T> {
-    value: T,
-    generation: usize,
-}
+    fn handles(&self) -> Box<dyn Iterator<Item = Handle> + '_> {
+        Box::new(SparseSet::handles(self))
+    }
 
-impl<T> Slot<T> {
-    pub const fn new(value: T, generation: usize) -> Self {
-        Self { value, generation }
+    fn ticks(&self, handle: Handle) -> Option<ComponentTicks> {
+        SparseSet::ticks(self, handle)
     }
 
-    pub const fn generation(&self) -> &usize {
-        &self.generation
+    fn set_ticks(&mut self, handle: Handle, ticks: ComponentTicks) {
+        SparseSet::set_ticks(self, handle, ticks)
     }
-}
 
-impl<T> Deref for Slot<T> {
-    type Target = T;
+    fn get_mut_with_ticks(
+        &mut self,
+        handle: Handle,
+    ) -> Option<(&mut T, Option<&mut ComponentTicks>)> {
+        SparseSet::get_mut_with_ticks(self, handle)
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.value
+    fn get_ptr(&self, handle: Handle) -> Option<(*const T, Option<*const ComponentTicks>)> {
+        let position = self.position(handle)?;
+        // Safety: positions are in bounds of both dense arrays,
+        // and only this element is borrowed
+        unsafe {
+            let ticks = &*self.ticks.as_ptr().add(position);
+            Some((
+                self.values.as_ptr().add(position),
+                ticks.as_ref().map(|ticks| ticks as *const _),
+            ))
+        }
     }
-}
 
-impl<T> DerefMut for Slot<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.value
+    fn get_mut_ptr(&mut self, handle: Handle) -> Option<(*mut T, Option<*mut ComponentTicks>)> {
+        let position = self.position(handle)?;
+        // Safety: positions are in bounds of both dense arrays,
+        // and only this element is borrowed
+        unsafe {
+            let ticks = &mut *self.ticks.as_mut_ptr().add(position);
+            Some((
+                self.values.as_mut_ptr().add(position),
+                ticks.as_mut().map(|ticks| ticks as *mut _),
+            ))
+        }
     }
 }
 
-pub struct Allocation {
-    allocated: bool,
-    generation: usize,
+/// Owns both the handles and the values of a single type,
+/// so inserting a value hands back its typed handle
+pub struct TypedArena<T> {
+    allocator: HandleAllocator,
+    elements: GenerationalVec<T>,
 }
 
-#[derive(Default)]
-pub struct HandleAllocator {
-    allocations: Vec<Allocation>,
-    available_handles: Vec<usize>,
+impl<T> Default for TypedArena<T> {
+    fn default() -> Self {
+        Self {
+            allocator: HandleAllocator::new(),
+            elements: GenerationalVec::new(SlotVec::default()),
+        }
+    }
 }
 
-impl HandleAllocator {
+impl<T> TypedArena<T> {
     pub fn new() -> Self {
         Self::default()
     }
 
-    pub fn allocate(&mut self) -> Handle {
-        match self.available_handles.pop() {
-            Some(index) => {
-                self.allocations[index].generation += 1;
-                self.allocations[index].allocated = true;
-                Handle {
-                    index,
-                    generation: self.allocations[index].generation,
-                }
-            }
-            None => {
-                self.allocations.push(Allocation {
-                    allocated: true,
-                    generation: 0,
-                });
-                Handle {
-                    index: self.allocations.len() - 1,
-                    generation: 0,
-                }
-            }
-        }
+    pub fn insert(&mut self, value: T) -> TypedHandle<T> {
+        let handle = self.allocator.allocate();
+        self.elements
+            .insert(handle, value)
+            .expect("A freshly allocated handle is never stale!");
+        TypedHandle::new(handle)
     }
 
-    pub fn deallocate(&mut self, handle: &Handle) {
-        if !self.is_allocated(handle) {
-            return;
+    pub fn remove(&mut self, handle: TypedHandle<T>) -> Option<T> {
+        if !self.contains(handle) {
+            return None;
         }
-        self.allocations[handle.index].allocated = false;
-        self.available_handles.push(handle.index);
+        self.allocator.deallocate(&handle.handle);
+        self.elements.take(handle.handle)
     }
 
-    pub fn is_allocated(&self, handle: &Handle) -> bool {
-        self.handle_exists(handle)
-            && self.allocations[handle.index].generation == handle.generation
-            && self.allocations[handle.index].allocated
+    pub fn contains(&self, handle: TypedHandle<T>) -> bool {
+        self.allocator.is_allocated(&handle.handle)
     }
 
-    pub fn handle_exists(&self, handle: &Handle) -> bool {
-        handle.index < self.allocations.len()
+    pub fn get(&self, handle: TypedHandle<T>) -> Option<&T> {
+        self.elements.get(handle.handle)
     }
 
-    pub fn allocated_handles(&self) -> Vec<Handle> {
-        self.allocations
+    pub fn get_mut(&mut self, handle: TypedHandle<T>) -> Option<&mut T> {
+        self.elements.get_mut(handle.handle)
+    }
+
+    pub fn len(&self) -> usize {
+        self.allocator.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allocator.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.allocator.clear();
+        self.elements.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TypedHandle<T>, &T)> {
+        self.elements
             .iter()
-            .enumerate()
-            .filter(|(_, allocation)| allocation.allocated)
-            .map(|(index, allocation)| Handle {
-                index,
-                generation: allocation.generation,
-            })
-            .collect()
+            .map(|(handle, value)| (TypedHandle::new(handle), value))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (TypedHandle<T>, &mut T)> {
+        self.elements
+            .iter_mut()
+            .map(|(handle, value)| (TypedHandle::new(handle), value))
     }
 }
 
@@ -370,4 +1459,470 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn iteration() -> Result<()> {
+        let mut allocator = HandleAllocator::new();
+        let mut vec = GenerationalVec::new(Vec::new());
+
+        let first = allocator.allocate();
+        let second = allocator.allocate();
+        let third = allocator.allocate();
+        vec.insert(first, 1)?;
+        vec.insert(second, 2)?;
+        vec.insert(third, 3)?;
+        vec.remove(second);
+
+        // Reusing a slot yields the handle with the new generation
+        allocator.deallocate(&second);
+        let reused = allocator.allocate();
+        vec.insert(reused, 4)?;
+
+        assert_eq!(vec.len(), 3);
+        assert_eq!(
+            vec.iter().collect::<Vec<_>>(),
+            [(first, &1), (reused, &4), (third, &3)]
+        );
+        assert_eq!(vec.handles().collect::<Vec<_>>(), [first, reused, third]);
+
+        for (_, value) in vec.iter_mut() {
+            *value *= 10;
+        }
+        for (handle, value) in &vec {
+            assert_eq!(vec.get(handle), Some(value));
+        }
+        assert_eq!(
+            vec.into_iter().collect::<Vec<_>>(),
+            [(first, 10), (reused, 40), (third, 30)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn drain_retain_and_clear() -> Result<()> {
+        let mut allocator = HandleAllocator::new();
+        let mut vec = GenerationalVec::new(Vec::new());
+        let handles = (0..4).map(|_| allocator.allocate()).collect::<Vec<_>>();
+        for (value, handle) in handles.iter().enumerate() {
+            vec.insert(*handle, value)?;
+        }
+
+        vec.retain(|_, value| *value % 2 == 0);
+        assert_eq!(vec.handles().collect::<Vec<_>>(), [handles[0], handles[2]]);
+
+        let drained = vec.drain().collect::<Vec<_>>();
+        assert_eq!(drained, [(handles[0], 0), (handles[2], 2)]);
+        assert!(vec.is_empty());
+
+        vec.insert(handles[1], 1)?;
+        vec.clear();
+        assert!(vec.is_empty());
+        assert!(vec.get(handles[1]).is_none());
+
+        // Stale handles are still rejected once their slots are vacant
+        allocator.deallocate(&handles[1]);
+        let reused = allocator.allocate();
+        assert_eq!(reused.index, handles[1].index);
+        vec.insert(reused, 5)?;
+        vec.clear();
+        assert!(vec.insert(handles[1], 1).is_err());
+        vec.insert(reused, 5)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn allocator_len_and_clear() {
+        let mut allocator = HandleAllocator::new();
+        assert!(allocator.is_empty());
+
+        let first = allocator.allocate();
+        let second = allocator.allocate();
+        assert_eq!(allocator.len(), 2);
+        assert_eq!(allocator.handles().collect::<Vec<_>>(), [first, second]);
+
+        allocator.clear();
+        assert!(allocator.is_empty());
+        assert!(!allocator.is_allocated(&first));
+
+        // Cleared slots come back with a new generation
+        let reused = allocator.allocate();
+        assert_ne!(reused, first);
+        assert_ne!(reused, second);
+        assert_eq!(allocator.len(), 1);
+    }
+
+    #[test]
+    fn typed_handles() {
+        struct Mesh;
+
+        let handle = HandleAllocator::new().allocate();
+        let typed_handle = TypedHandle::<Mesh>::from(handle);
+        assert_eq!(typed_handle.handle(), handle);
+        assert_eq!(Handle::from(typed_handle), handle);
+        assert_eq!(
+            std::mem::size_of::<TypedHandle<Mesh>>(),
+            std::mem::size_of::<Handle>()
+        );
+
+        let copied = typed_handle;
+        assert_eq!(copied, typed_handle);
+        let set = std::collections::HashSet::from([typed_handle, copied]);
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn typed_arena() {
+        let mut arena = TypedArena::new();
+        let first = arena.insert("first");
+        let second = arena.insert("second");
+        assert_eq!(arena.len(), 2);
+        assert_eq!(arena.get(first), Some(&"first"));
+
+        if let Some(value) = arena.get_mut(second) {
+            *value = "changed";
+        }
+        assert_eq!(arena.get(second), Some(&"changed"));
+
+        assert_eq!(arena.remove(first), Some("first"));
+        assert_eq!(arena.remove(first), None);
+        assert!(!arena.contains(first));
+
+        // A stale handle doesn't resolve to the value that reused its slot
+        let third = arena.insert("third");
+        assert_eq!(third.handle().index(), first.handle().index());
+        assert_eq!(arena.get(first), None);
+        assert_eq!(
+            arena.iter().collect::<Vec<_>>(),
+            [(third, &"third"), (second, &"changed")]
+        );
+
+        arena.clear();
+        assert!(arena.is_empty());
+        assert_eq!(arena.get(third), None);
+    }
+
+    #[test]
+    fn handle_bits() {
+        assert_eq!(std::mem::size_of::<Handle>(), 8);
+
+        let handle = Handle {
+            index: 7,
+            generation: 3,
+        };
+        assert_eq!(handle.to_bits(), 3 << 32 | 7);
+        assert_eq!(Handle::from_bits(handle.to_bits()), handle);
+
+        let max = Handle {
+            index: u32::MAX,
+            generation: u32::MAX,
+        };
+        assert_eq!(Handle::from_bits(max.to_bits()), max);
+    }
+
+    #[test]
+    fn exhausted_generations_are_retired() {
+        let mut allocator = HandleAllocator::new();
+        let handle = allocator.allocate();
+        allocator.allocations[handle.slot()].generation = u32::MAX - 1;
+        let handle = Handle {
+            generation: u32::MAX - 1,
+            ..handle
+        };
+
+        // The last generation is still handed out
+        allocator.deallocate(&handle);
+        let last = allocator.allocate();
+        assert_eq!(*last.index(), *handle.index());
+        assert_eq!(*last.generation(), u32::MAX);
+
+        // After that the slot is never reused
+        allocator.deallocate(&last);
+        let next = allocator.allocate();
+        assert_ne!(next.index(), last.index());
+        assert!(!allocator.is_allocated(&last));
+        assert_eq!(allocator.len(), 1);
+    }
+
+    #[test]
+    fn exhausted_indices_reserve_invalid_handles() {
+        let reserver = HandleReserver {
+            next_index: Arc::new(AtomicU32::new(u32::MAX - 1)),
+            ..Default::default()
+        };
+        assert_eq!(*reserver.reserve().index(), u32::MAX - 1);
+        assert_eq!(reserver.reserve(), Handle::INVALID);
+        assert_eq!(reserver.reserve(), Handle::INVALID);
+        assert_eq!(reserver.next_index(), u32::MAX as usize);
+
+        let mut allocator = HandleAllocator {
+            reserver,
+            ..Default::default()
+        };
+        assert_eq!(allocator.reserve(), Handle::INVALID);
+        assert_eq!(allocator.allocate(), Handle::INVALID);
+        assert!(!allocator.is_allocated(&Handle::INVALID));
+
+        // Storages refuse the invalid handle instead of growing to fit it
+        assert!(GenerationalVec::new(Vec::new())
+            .insert(Handle::INVALID, 0)
+            .is_err());
+        assert!(SparseSet::new().insert(Handle::INVALID, 0).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() -> Result<()> {
+        let mut allocator = HandleAllocator::new();
+        let mut vec = GenerationalVec::new(Vec::new());
+        let handles = (0..4).map(|_| allocator.allocate()).collect::<Vec<_>>();
+        for (value, handle) in handles.iter().enumerate() {
+            vec.insert(*handle, value)?;
+        }
+        vec.remove(handles[1]);
+        allocator.deallocate(&handles[1]);
+
+        let allocator_json = serde_json::to_string(&allocator)?;
+        let vec_json = serde_json::to_string(&vec)?;
+        let loaded_allocator = serde_json::from_str::<HandleAllocator>(&allocator_json)?;
+        let loaded_vec = serde_json::from_str::<GenerationalVec<usize>>(&vec_json)?;
+
+        assert_eq!(serde_json::to_string(&loaded_allocator)?, allocator_json);
+        assert_eq!(serde_json::to_string(&loaded_vec)?, vec_json);
+        assert_eq!(
+            loaded_allocator.allocated_handles(),
+            allocator.allocated_handles()
+        );
+        assert_eq!(
+            loaded_vec.iter().collect::<Vec<_>>(),
+            vec.iter().collect::<Vec<_>>()
+        );
+
+        let typed_handle = TypedHandle::<String>::new(handles[2]);
+        let typed_json = serde_json::to_string(&typed_handle)?;
+        assert_eq!(typed_json, serde_json::to_string(&handles[2])?);
+        assert_eq!(
+            serde_json::from_str::<TypedHandle<String>>(&typed_json)?,
+            typed_handle
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn handles_allocated_after_reload_never_alias() -> Result<()> {
+        let mut allocator = HandleAllocator::new();
+        let mut issued = (0..8).map(|_| allocator.allocate()).collect::<Vec<_>>();
+        for handle in issued.iter().step_by(2) {
+            allocator.deallocate(handle);
+        }
+        let alive = allocator.allocated_handles();
+
+        let mut loaded =
+            serde_json::from_str::<HandleAllocator>(&serde_json::to_string(&allocator)?)?;
+        for handle in alive.iter() {
+            assert!(loaded.is_allocated(handle));
+        }
+
+        for _ in 0..16 {
+            let handle = loaded.allocate();
+            assert!(!issued.contains(&handle));
+            issued.push(handle);
+        }
+        for handle in alive.iter() {
+            assert!(loaded.is_allocated(handle));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn pending_reservations_are_saved_as_free() -> Result<()> {
+        let mut allocator = HandleAllocator::new();
+        let alive = allocator.allocate();
+        let freed = allocator.allocate();
+        allocator.deallocate(&freed);
+
+        // One reservation reuses the freed slot, and the next one leaves
+        // a placeholder behind when a new slot is allocated after it
+        let reserved_free = allocator.reserve();
+        let reserved_new = allocator.reserve();
+        let allocated = allocator.allocate();
+        assert_eq!(*reserved_free.index(), *freed.index());
+        assert!(allocator.has_pending_reservations());
+
+        let mut loaded =
+            serde_json::from_str::<HandleAllocator>(&serde_json::to_string(&allocator)?)?;
+        assert!(!loaded.has_pending_reservations());
+        assert_eq!(loaded.allocated_handles(), [alive, allocated]);
+
+        // Both reserved slots are handed out again instead of leaking
+        let mut reused = [loaded.allocate(), loaded.allocate()].map(|handle| *handle.index());
+        reused.sort();
+        assert_eq!(reused, [*reserved_free.index(), *reserved_new.index()]);
+        assert_eq!(*loaded.allocate().index(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sparse_set() -> Result<()> {
+        let mut allocator = HandleAllocator::new();
+        let mut set = SparseSet::new();
+        let handles = (0..4).map(|_| allocator.allocate()).collect::<Vec<_>>();
+
+        set.insert(handles[3], "d")?;
+        set.insert(handles[1], "b")?;
+        set.insert(handles[2], "c")?;
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.get(handles[1]), Some(&"b"));
+        assert_eq!(set.get(handles[0]), None);
+
+        // Removal swaps the last element into the freed position
+        assert_eq!(set.take(handles[3]), Some("d"));
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            [(handles[2], &"c"), (handles[1], &"b")]
+        );
+        assert_eq!(set.get(handles[2]), Some(&"c"));
+
+        if let Some(value) = set.get_mut(handles[2]) {
+            *value = "changed";
+        }
+        assert_eq!(set.values(), ["changed", "b"]);
+
+        set.clear();
+        assert!(set.is_empty());
+        assert_eq!(set.get(handles[1]), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sparse_set_generations() -> Result<()> {
+        let mut allocator = HandleAllocator::new();
+        let mut set = SparseSet::new();
+
+        let handle = allocator.allocate();
+        set.insert(handle, 1)?;
+        allocator.deallocate(&handle);
+        let reused = allocator.allocate();
+
+        // Stale handles neither read nor remove the newer value
+        set.insert(reused, 2)?;
+        assert_eq!(set.get(handle), None);
+        set.remove(handle);
+        assert_eq!(set.get(reused), Some(&2));
+        assert_eq!(set.len(), 1);
+
+        // Inserting with an older generation than the stored one fails
+        assert!(set.insert(handle, 3).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn storages_are_interchangeable() -> Result<()> {
+        fn exercise(storage: &mut dyn Storage<u32>) -> Result<()> {
+            let mut allocator = HandleAllocator::new();
+            let first = allocator.allocate();
+            let second = allocator.allocate();
+            storage.insert(first, 1)?;
+            storage.insert(second, 2)?;
+            *storage.get_mut(second).unwrap() += 1;
+            assert_eq!(storage.take(first), Some(1));
+            assert_eq!(storage.get(second), Some(&3));
+            assert_eq!(storage.handles().collect::<Vec<_>>(), [second]);
+            assert_eq!(storage.len(), 1);
+            storage.clear();
+            assert!(storage.is_empty());
+            Ok(())
+        }
+
+        exercise(&mut GenerationalVec::new(Vec::new()))?;
+        exercise(&mut SparseSet::new())
+    }
+
+    #[test]
+    fn concurrent_reservation() {
+        let mut allocator = HandleAllocator::new();
+        let existing = allocator.allocate();
+        let freed = allocator.allocate();
+        allocator.deallocate(&freed);
+
+        let reserved = std::thread::scope(|scope| {
+            let workers = (0..8)
+                .map(|_| scope.spawn(|| (0..1000).map(|_| allocator.reserve()).collect::<Vec<_>>()))
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        let unique = reserved
+            .iter()
+            .copied()
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(unique.len(), reserved.len());
+        assert!(!unique.contains(&existing));
+        assert!(!unique.contains(&freed));
+
+        // Reserved handles only become allocated once flushed
+        assert!(allocator.has_pending_reservations());
+        assert!(!allocator.is_allocated(&reserved[0]));
+        allocator.flush();
+        assert!(!allocator.has_pending_reservations());
+        assert!(reserved.iter().all(|handle| allocator.is_allocated(handle)));
+        assert_eq!(allocator.len(), reserved.len() + 1);
+
+        // Regular allocation never hands out a reserved handle
+        for _ in 0..4 {
+            assert!(!unique.contains(&allocator.allocate()));
+        }
+    }
+
+    #[test]
+    fn reservations_reuse_freed_slots() {
+        let mut allocator = HandleAllocator::new();
+        let reserver = allocator.reserver();
+        for _ in 0..100 {
+            let handle = reserver.reserve();
+            allocator.flush();
+            assert!(allocator.is_allocated(&handle));
+            allocator.deallocate(&handle);
+        }
+        assert_eq!(allocator.allocations.len(), 1);
+        assert_eq!(*allocator.reserve().generation(), 100);
+    }
+
+    #[test]
+    fn detached_reserver() {
+        let mut allocator = HandleAllocator::new();
+        let reserver = allocator.reserver();
+
+        let workers = (0..4)
+            .map(|_| {
+                let reserver = reserver.clone();
+                std::thread::spawn(move || (0..100).map(|_| reserver.reserve()).collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+
+        // Allocating while workers reserve never produces duplicates
+        let mut handles = (0..100).map(|_| allocator.allocate()).collect::<Vec<_>>();
+        for worker in workers {
+            handles.extend(worker.join().unwrap());
+        }
+        allocator.flush();
+
+        let unique = handles
+            .iter()
+            .copied()
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(unique.len(), 500);
+        assert!(handles.iter().all(|handle| allocator.is_allocated(handle)));
+    }
 }
@@ -12,8 +12,9 @@ impl<'window> Renderer<'window> {
         window: impl Into<wgpu::SurfaceTarget<'window>>,
         width: u32,
         height: u32,
+        present_mode: wgpu::PresentMode,
     ) -> Self {
-        let gpu = Gpu::new_async(window, width, height).await;
+        let gpu = Gpu::new_async(window, width, height, present_mode).await;
         let depth_texture_view = gpu.create_depth_texture(width, height);
 
         let egui_renderer = egui_wgpu::Renderer::new(
@@ -193,6 +194,7 @@ impl<'window> Gpu<'window> {
         window: impl Into<wgpu::SurfaceTarget<'window>>,
         width: u32,
         height: u32,
+        present_mode: wgpu::PresentMode,
     ) -> Self {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::util::backend_bits_from_env().unwrap_or_else(wgpu::Backends::all),
@@ -253,12 +255,22 @@ impl<'window> Gpu<'window> {
             .find(|f| !f.is_srgb()) // egui wants a non-srgb surface texture
             .unwrap_or(surface_capabilities.formats[0]);
 
+        // The automatic modes are always supported, falling back as needed
+        let present_mode = match present_mode {
+            wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync => present_mode,
+            _ if surface_capabilities.present_modes.contains(&present_mode) => present_mode,
+            _ => {
+                log::warn!("Present mode {present_mode:?} is not supported, using vsync instead");
+                wgpu::PresentMode::AutoVsync
+            }
+        };
+
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width,
             height,
-            present_mode: surface_capabilities.present_modes[0],
+            present_mode,
             alpha_mode: surface_capabilities.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
@@ -416,7 +428,7 @@ impl Vertex {
         wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4].to_vec()
     }
 
-    pub fn description(attributes: &[wgpu::VertexAttribute]) -> wgpu::VertexBufferLayout {
+    pub fn description(attributes: &[wgpu::VertexAttribute]) -> wgpu::VertexBufferLayout<'_> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
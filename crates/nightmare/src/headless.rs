@@ -0,0 +1,349 @@
+use crate::app::{App, Context};
+
+/// Runs an app without a window or renderer, on a simulated clock.
+///
+/// Every frame goes through the same fixed updates, `App::update`, commands,
+/// systems and events as a windowed app, and the gui still runs so UI logic
+/// can be exercised. Nothing is drawn and no winit events are received,
+/// though `context.io` can be written to directly between frames
+/// and gamepads can be driven through a `FakeGamepadBackend`.
+pub struct HeadlessApp<A: App> {
+    pub state: A,
+    pub context: Context,
+    /// The simulated time between frames
+    pub frame_time: crate::Duration,
+    /// The gui screen size in points
+    pub screen_size: (f32, f32),
+    gui_context: egui::Context,
+    gui_events: Vec<egui::Event>,
+    last_gui_output: Option<egui::FullOutput>,
+    start: crate::Instant,
+    elapsed: crate::Duration,
+    frames: u64,
+}
+
+impl<A: App> HeadlessApp<A> {
+    /// Initializes the app, simulating 60 frames per second
+    pub fn new(mut state: A) -> Self {
+        let mut context = Context::new();
+        state.initialize(&mut context);
+        Self {
+            state,
+            context,
+            frame_time: crate::Duration::from_secs_f64(1.0 / 60.0),
+            screen_size: (1280.0, 720.0),
+            gui_context: egui::Context::default(),
+            gui_events: Vec::new(),
+            last_gui_output: None,
+            start: crate::Instant::now(),
+            elapsed: crate::Duration::ZERO,
+            frames: 0,
+        }
+    }
+
+    /// Runs a single frame, returning whether the app wants to keep running
+    pub fn run_frame(&mut self) -> bool {
+        if self.context.exit_requested() {
+            return false;
+        }
+
+        self.context.delta_time = self.frame_time;
+        self.elapsed += self.frame_time;
+        self.frames += 1;
+        let now = self.now();
+
+        let (width, height) = self.screen_size;
+        self.gui_context.begin_frame(egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(width, height),
+            )),
+            time: Some(self.elapsed.as_secs_f64()),
+            predicted_dt: self.frame_time.as_secs_f32(),
+            events: std::mem::take(&mut self.gui_events),
+            ..Default::default()
+        });
+        crate::app::update_frame(&mut self.state, &mut self.context, &self.gui_context, now);
+        self.last_gui_output = Some(self.gui_context.end_frame());
+
+        // Input written between frames is seen by exactly one frame,
+        // the same way winit events arrive before the frame they belong to
+        self.context.io.new_frame(now + self.frame_time);
+
+        !self.context.exit_requested()
+    }
+
+    /// Runs up to `frames` frames, stopping early if the app exits.
+    /// Returns the number of frames that were run.
+    pub fn run(&mut self, frames: u64) -> u64 {
+        let start = self.frames;
+        for _ in 0..frames {
+            if !self.run_frame() {
+                break;
+            }
+        }
+        self.frames - start
+    }
+
+    /// Queues gui input, such as pointer or key events, for the next frame
+    pub fn send_gui_event(&mut self, event: egui::Event) {
+        self.gui_events.push(event);
+    }
+
+    pub fn gui_context(&self) -> &egui::Context {
+        &self.gui_context
+    }
+
+    /// What the gui produced on the last frame, which is never painted
+    pub fn last_gui_output(&self) -> Option<&egui::FullOutput> {
+        self.last_gui_output.as_ref()
+    }
+
+    /// The simulated time since the first frame
+    pub fn elapsed(&self) -> crate::Duration {
+        self.elapsed
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// The simulated instant of the current frame
+    pub fn now(&self) -> crate::Instant {
+        self.start + self.elapsed
+    }
+
+    pub fn into_state(self) -> A {
+        self.state
+    }
+}
+
+/// Runs an app headless for up to `frames` frames and returns its final state
+pub fn run_headless<A: App>(state: A, frames: u64) -> A {
+    let mut app = HeadlessApp::new(state);
+    app.run(frames);
+    app.into_state()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gamepad::{FakeGamepadBackend, GamepadButton, GamepadId},
+        schedule::{ResMut, Stage},
+    };
+
+    #[derive(Default)]
+    struct Counter {
+        updates: u32,
+        fixed_updates: u32,
+        clicks: u32,
+        button: Option<egui::Rect>,
+        exit_after: Option<u32>,
+    }
+
+    struct SystemRuns(u32);
+
+    impl App for Counter {
+        fn initialize(&mut self, context: &mut Context) {
+            context.world_mut().insert_resource(SystemRuns(0));
+            context
+                .schedule
+                .add_system(Stage::Update, "count", |mut runs: ResMut<SystemRuns>| {
+                    runs.0 += 1;
+                });
+        }
+
+        fn fixed_update(&mut self, _context: &mut Context) {
+            self.fixed_updates += 1;
+        }
+
+        fn update(&mut self, context: &mut Context, ui: &egui::Context) {
+            self.updates += 1;
+            egui::CentralPanel::default().show(ui, |ui| {
+                let response = ui.button("Click");
+                self.button = Some(response.rect);
+                if response.clicked() {
+                    self.clicks += 1;
+                }
+            });
+            if self.exit_after == Some(self.updates) {
+                context.exit();
+            }
+        }
+    }
+
+    #[test]
+    fn runs_frames_on_a_simulated_clock() {
+        let mut app = HeadlessApp::new(Counter::default());
+        app.frame_time = crate::Duration::from_secs_f64(1.0 / 30.0);
+        assert_eq!(app.run(10), 10);
+
+        assert_eq!(app.state.updates, 10);
+        assert_eq!(app.context.world().resource::<SystemRuns>().unwrap().0, 10);
+        assert!((19..=20).contains(&app.state.fixed_updates));
+        assert!((app.elapsed().as_secs_f64() - 1.0 / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stops_when_the_app_exits() {
+        let state = Counter {
+            exit_after: Some(3),
+            ..Default::default()
+        };
+        let mut app = HeadlessApp::new(state);
+        assert_eq!(app.run(100), 3);
+        assert!(!app.run_frame());
+        assert_eq!(run_headless(Counter::default(), 5).updates, 5);
+    }
+
+    #[test]
+    fn gui_receives_input() {
+        let mut app = HeadlessApp::new(Counter::default());
+        app.run(1);
+        let position = app.state.button.unwrap().center();
+
+        app.send_gui_event(egui::Event::PointerMoved(position));
+        app.run(1);
+        for pressed in [true, false] {
+            app.send_gui_event(egui::Event::PointerButton {
+                pos: position,
+                button: egui::PointerButton::Primary,
+                pressed,
+                modifiers: egui::Modifiers::default(),
+            });
+            app.run(1);
+        }
+
+        assert_eq!(app.state.clicks, 1);
+        assert!(app.last_gui_output().is_some());
+    }
+
+    #[test]
+    fn gamepads_are_polled_every_frame() {
+        struct Jumper {
+            jumps: u32,
+        }
+
+        impl App for Jumper {
+            fn update(&mut self, context: &mut Context, _ui: &egui::Context) {
+                let gamepads = &context.io.gamepads;
+                if gamepads
+                    .iter()
+                    .any(|(_, gamepad)| gamepad.just_pressed(GamepadButton::South))
+                {
+                    self.jumps += 1;
+                }
+            }
+        }
+
+        let backend = FakeGamepadBackend::new();
+        let mut app = HeadlessApp::new(Jumper { jumps: 0 });
+        app.context.io.gamepads.set_backend(backend.clone());
+        backend.connect(GamepadId(0), "Pad");
+        backend.press(GamepadId(0), GamepadButton::South);
+        app.run(3);
+
+        assert_eq!(app.state.jumps, 1);
+        let gamepad = app.context.io.gamepads.get(GamepadId(0)).unwrap();
+        assert!(gamepad.buttons.press_duration(GamepadButton::South) >= Some(app.frame_time));
+    }
+
+    #[test]
+    fn keys_are_just_pressed_for_one_frame() {
+        #[derive(Default)]
+        struct Watcher {
+            key_presses: Vec<bool>,
+            click_presses: Vec<bool>,
+            mouse_deltas: Vec<f32>,
+        }
+
+        impl App for Watcher {
+            fn update(&mut self, context: &mut Context, _ui: &egui::Context) {
+                let io = &context.io;
+                self.key_presses
+                    .push(io.is_key_just_pressed(winit::keyboard::KeyCode::Space));
+                self.click_presses.push(
+                    io.mouse
+                        .buttons
+                        .just_pressed(winit::event::MouseButton::Left),
+                );
+                self.mouse_deltas.push(io.mouse.position_delta.x);
+            }
+        }
+
+        let key_code = winit::keyboard::KeyCode::Space;
+        let mut app = HeadlessApp::new(Watcher::default());
+        app.run(1);
+
+        // Keys arrive the way a window delivers them, stamped with the simulated clock
+        let now = app.now();
+        app.context.io.receive_key_input_at(
+            crate::text::KeyInput {
+                physical_key: winit::keyboard::PhysicalKey::Code(key_code),
+                logical_key: winit::keyboard::Key::Named(winit::keyboard::NamedKey::Space),
+                text: Some(" ".into()),
+                state: winit::event::ElementState::Pressed,
+                repeat: false,
+            },
+            now,
+        );
+        app.context
+            .io
+            .mouse
+            .buttons
+            .press(winit::event::MouseButton::Left, now);
+        app.context.io.mouse.position_delta = nalgebra_glm::vec2(5.0, 0.0);
+        app.run(2);
+
+        assert_eq!(app.state.key_presses, [false, true, false]);
+        assert_eq!(app.state.click_presses, [false, true, false]);
+        assert_eq!(app.state.mouse_deltas, [0.0, 5.0, 0.0]);
+
+        // Hold times follow the simulated clock
+        app.run(57);
+        assert_eq!(
+            app.context.io.keys.press_duration(key_code),
+            Some(app.frame_time * 60)
+        );
+    }
+
+    #[test]
+    fn text_input_is_cleared_every_frame() {
+        #[derive(Default)]
+        struct Typist {
+            typed: Vec<String>,
+        }
+
+        impl App for Typist {
+            fn update(&mut self, context: &mut Context, _ui: &egui::Context) {
+                self.typed.push(context.io.text().to_string());
+            }
+        }
+
+        let mut app = HeadlessApp::new(Typist::default());
+        let now = app.now();
+        app.context.io.receive_key_input_at(
+            crate::text::KeyInput {
+                physical_key: winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::KeyA),
+                logical_key: winit::keyboard::Key::Character("a".into()),
+                text: Some("a".into()),
+                state: winit::event::ElementState::Pressed,
+                repeat: false,
+            },
+            now,
+        );
+        app.context
+            .io
+            .text_input
+            .receive_ime_event(crate::text::ImeEvent::Commit("日本".into()));
+        assert_eq!(app.context.io.text(), "a日本");
+
+        app.run(2);
+        assert_eq!(app.state.typed, ["a日本", ""]);
+        let text_input = &app.context.io.text_input;
+        assert_eq!(text_input.text(), "");
+        assert!(text_input.key_events().is_empty());
+        assert!(text_input.ime_events().is_empty());
+    }
+}
@@ -0,0 +1,418 @@
+use crate::{
+    genvec::{error::HandleNotFoundError, Result},
+    world::{Entity, With, Without, World},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    pub translation: nalgebra_glm::Vec3,
+    pub rotation: nalgebra_glm::Quat,
+    pub scale: nalgebra_glm::Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: nalgebra_glm::Vec3::zeros(),
+            rotation: nalgebra_glm::quat_identity(),
+            scale: nalgebra_glm::vec3(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation: nalgebra_glm::Vec3) -> Self {
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    /// The local transformation matrix, applying scale, then rotation, then translation
+    pub fn matrix(&self) -> nalgebra_glm::Mat4 {
+        nalgebra_glm::translation(&self.translation)
+            * nalgebra_glm::quat_to_mat4(&self.rotation)
+            * nalgebra_glm::scaling(&self.scale)
+    }
+}
+
+/// The world space matrix of an entity, written by `propagate_transforms`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlobalTransform(pub nalgebra_glm::Mat4);
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        Self(nalgebra_glm::Mat4::identity())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Parent(pub Entity);
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Children(pub Vec<Entity>);
+
+#[derive(Debug)]
+pub struct HierarchyCycleError {
+    pub child: Entity,
+    pub parent: Entity,
+}
+
+impl std::error::Error for HierarchyCycleError {}
+
+impl std::fmt::Display for HierarchyCycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Entity '{:?}' cannot be parented to its descendant '{:?}'.",
+            self.child, self.parent
+        )
+    }
+}
+
+impl World {
+    /// Attaches `child` to `parent`, detaching it from any previous parent
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> Result<()> {
+        for handle in [child, parent] {
+            if !self.is_alive(handle) {
+                return Err(Box::new(HandleNotFoundError { handle }));
+            }
+        }
+
+        if self.ancestors(parent).contains(&child) || child == parent {
+            return Err(Box::new(HierarchyCycleError { child, parent }));
+        }
+
+        self.remove_parent(child);
+        self.add_component(child, Parent(parent))?;
+        match self.get_component_mut::<Children>(parent) {
+            Some(children) => children.0.push(child),
+            None => self.add_component(parent, Children(vec![child]))?,
+        }
+        Ok(())
+    }
+
+    /// Detaches `child` from its parent, making it a root
+    pub fn remove_parent(&mut self, child: Entity) {
+        let Some(Parent(parent)) = self.get_component::<Parent>(child).copied() else {
+            return;
+        };
+        self.remove_component::<Parent>(child);
+        if let Some(children) = self.get_component_mut::<Children>(parent) {
+            children.0.retain(|entity| *entity != child);
+        }
+    }
+
+    /// The parent of an entity, then its parent's parent, and so on up to the root.
+    /// A cycle of `Parent` components, which `add_component` does not prevent,
+    /// ends the walk before any entity is repeated.
+    pub fn ancestors(&self, entity: Entity) -> Vec<Entity> {
+        let mut ancestors = Vec::new();
+        let mut current = entity;
+        while let Some(Parent(parent)) = self.get_component::<Parent>(current) {
+            if *parent == entity || ancestors.contains(parent) {
+                break;
+            }
+            ancestors.push(*parent);
+            current = *parent;
+        }
+        ancestors
+    }
+
+    /// Despawns an entity along with all of its descendants, deepest first.
+    /// Entities reached more than once through a cycle of `Children` are despawned once.
+    pub fn despawn_recursive(&mut self, entity: Entity) {
+        let mut visited = std::collections::HashSet::from([entity]);
+        let mut stack = vec![entity];
+        let mut descendants = Vec::new();
+        while let Some(current) = stack.pop() {
+            descendants.push(current);
+            if let Some(Children(children)) = self.get_component::<Children>(current) {
+                stack.extend(children.iter().filter(|child| visited.insert(**child)));
+            }
+        }
+        for descendant in descendants.into_iter().rev() {
+            self.despawn(descendant);
+        }
+    }
+
+    /// Removes a despawned entity from the hierarchy,
+    /// handing its children over to its own parent in its place
+    pub(crate) fn detach_from_hierarchy(&mut self, entity: Entity) {
+        let parent = self.get_component::<Parent>(entity).copied();
+        let position = parent.and_then(|Parent(parent)| {
+            self.get_component::<Children>(parent)?
+                .0
+                .iter()
+                .position(|sibling| *sibling == entity)
+        });
+        self.remove_parent(entity);
+
+        let children = self
+            .get_component::<Children>(entity)
+            .map(|children| children.0.clone())
+            .unwrap_or_default();
+        for child in children.iter() {
+            self.remove_parent(*child);
+        }
+
+        if let (Some(Parent(parent)), Some(position)) = (parent, position) {
+            for child in children.iter() {
+                if let Err(error) = self.add_component(*child, Parent(parent)) {
+                    log::error!("Failed to reparent child '{child:?}': {error}");
+                }
+            }
+            if let Some(siblings) = self.get_component_mut::<Children>(parent) {
+                siblings.0.splice(position..position, children);
+            }
+        }
+    }
+}
+
+/// Computes the `GlobalTransform` of every entity with a `Transform`,
+/// walking the hierarchy from the roots down.
+/// Only global transforms whose matrix differs are written, so `Changed<GlobalTransform>`
+/// matches just the entities that moved.
+/// Entities whose `Transform` was removed lose their `GlobalTransform` too.
+pub fn propagate_transforms(world: &mut World) {
+    let stale = world
+        .query_filtered::<Entity, (With<GlobalTransform>, Without<Transform>)>()
+        .collect::<Vec<_>>();
+    for entity in stale {
+        world.remove_component::<GlobalTransform>(entity);
+    }
+
+    let mut stack = world
+        .query_filtered::<Entity, Without<Parent>>()
+        .map(|entity| (entity, nalgebra_glm::Mat4::identity()))
+        .collect::<Vec<_>>();
+
+    // Guards against cycles of `Children` components added by hand
+    let mut visited = std::collections::HashSet::new();
+    while let Some((entity, parent_matrix)) = stack.pop() {
+        if !visited.insert(entity) {
+            continue;
+        }
+
+        // Entities without a transform pass their parent's matrix straight through
+        let matrix = match world.get_component::<Transform>(entity) {
+            Some(transform) => {
+                let matrix = parent_matrix * transform.matrix();
+                match world.get_component::<GlobalTransform>(entity) {
+                    Some(global_transform) if global_transform.0 == matrix => {}
+                    Some(_) => {
+                        if let Some(global_transform) =
+                            world.get_component_mut::<GlobalTransform>(entity)
+                        {
+                            global_transform.0 = matrix;
+                        }
+                    }
+                    None => {
+                        if let Err(error) = world.add_component(entity, GlobalTransform(matrix)) {
+                            log::error!(
+                                "Failed to add a global transform to '{entity:?}': {error}"
+                            );
+                        }
+                    }
+                }
+                matrix
+            }
+            None => parent_matrix,
+        };
+
+        if let Some(children) = world.get_component::<Children>(entity) {
+            stack.extend(children.0.iter().map(|child| (*child, matrix)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn global_translation(world: &World, entity: Entity) -> nalgebra_glm::Vec3 {
+        let matrix = world.get_component::<GlobalTransform>(entity).unwrap().0;
+        nalgebra_glm::vec3(matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)])
+    }
+
+    fn spawn_at(world: &mut World, x: f32) -> Result<Entity> {
+        let entity = world.spawn();
+        world.add_component(
+            entity,
+            Transform::from_translation(nalgebra_glm::vec3(x, 0.0, 0.0)),
+        )?;
+        Ok(entity)
+    }
+
+    #[test]
+    fn global_transforms_propagate_down() -> Result<()> {
+        let mut world = World::new();
+        let root = spawn_at(&mut world, 1.0)?;
+        let child = spawn_at(&mut world, 2.0)?;
+        let grandchild = spawn_at(&mut world, 3.0)?;
+        world.set_parent(child, root)?;
+        world.set_parent(grandchild, child)?;
+
+        if let Some(transform) = world.get_component_mut::<Transform>(root) {
+            transform.scale = nalgebra_glm::vec3(2.0, 2.0, 2.0);
+        }
+        propagate_transforms(&mut world);
+
+        assert_eq!(global_translation(&world, root).x, 1.0);
+        assert_eq!(global_translation(&world, child).x, 5.0);
+        assert_eq!(global_translation(&world, grandchild).x, 11.0);
+
+        // Removing a transform also removes the global transform computed from it
+        world.remove_component::<Transform>(grandchild);
+        propagate_transforms(&mut world);
+        assert!(world.get_component::<GlobalTransform>(grandchild).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn unchanged_global_transforms_are_not_marked_changed() -> Result<()> {
+        use crate::world::Changed;
+
+        let mut world = World::new();
+        let root = spawn_at(&mut world, 1.0)?;
+        let child = spawn_at(&mut world, 2.0)?;
+        let other = spawn_at(&mut world, 3.0)?;
+        world.set_parent(child, root)?;
+        propagate_transforms(&mut world);
+
+        let changed = |world: &mut World| {
+            let tick = world.increment_change_tick();
+            world.set_last_run_tick(tick);
+            propagate_transforms(world);
+            world
+                .query_filtered::<Entity, Changed<GlobalTransform>>()
+                .collect::<Vec<_>>()
+        };
+        assert!(changed(&mut world).is_empty());
+
+        if let Some(transform) = world.get_component_mut::<Transform>(root) {
+            transform.translation.x = 5.0;
+        }
+        let mut moved = changed(&mut world);
+        moved.sort_by_key(|entity| *entity.index());
+        assert_eq!(moved, [root, child]);
+        assert_eq!(global_translation(&world, other).x, 3.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parent_cycles_end_the_ancestor_walk() -> Result<()> {
+        let mut world = World::new();
+        let first = world.spawn();
+        let second = world.spawn();
+        world.add_component(first, Parent(second))?;
+        world.add_component(second, Parent(first))?;
+
+        assert_eq!(world.ancestors(first), [second]);
+        assert!(world.set_parent(first, second).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn children_cycles_are_despawned_once() -> Result<()> {
+        let mut world = World::new();
+        let first = world.spawn();
+        let second = world.spawn();
+        let bystander = world.spawn();
+        world.add_component(first, Children(vec![second]))?;
+        world.add_component(second, Children(vec![first, second]))?;
+
+        world.despawn_recursive(first);
+        assert_eq!(world.entities(), [bystander]);
+        Ok(())
+    }
+
+    #[test]
+    fn reparenting() -> Result<()> {
+        let mut world = World::new();
+        let first = spawn_at(&mut world, 0.0)?;
+        let second = spawn_at(&mut world, 0.0)?;
+        let child = spawn_at(&mut world, 0.0)?;
+
+        world.set_parent(child, first)?;
+        world.set_parent(child, second)?;
+        assert_eq!(world.get_component(child), Some(&Parent(second)));
+        assert_eq!(world.get_component(first), Some(&Children(Vec::new())));
+        assert_eq!(world.get_component(second), Some(&Children(vec![child])));
+
+        // Parenting an entity to its own descendant is rejected
+        assert!(world.set_parent(second, child).is_err());
+        assert!(world.set_parent(child, child).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn despawning_a_parent_reparents_its_children() -> Result<()> {
+        let mut world = World::new();
+        let root = spawn_at(&mut world, 0.0)?;
+        let middle = spawn_at(&mut world, 0.0)?;
+        let leaf = spawn_at(&mut world, 0.0)?;
+        world.set_parent(middle, root)?;
+        world.set_parent(leaf, middle)?;
+
+        world.despawn(middle);
+        assert_eq!(world.get_component(leaf), Some(&Parent(root)));
+        assert_eq!(world.get_component(root), Some(&Children(vec![leaf])));
+
+        world.despawn(root);
+        assert!(world.get_component::<Parent>(leaf).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reparented_children_keep_their_place() -> Result<()> {
+        let mut world = World::new();
+        let root = spawn_at(&mut world, 0.0)?;
+        let [first, middle, last, left, right] = [(); 5].map(|_| world.spawn());
+        for child in [first, middle, last] {
+            world.set_parent(child, root)?;
+        }
+        world.set_parent(left, middle)?;
+        world.set_parent(right, middle)?;
+
+        world.despawn(middle);
+        assert_eq!(
+            world.get_component(root),
+            Some(&Children(vec![first, left, right, last]))
+        );
+        assert_eq!(world.get_component(left), Some(&Parent(root)));
+        assert_eq!(world.get_component(right), Some(&Parent(root)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn despawn_recursive() -> Result<()> {
+        let mut world = World::new();
+        let root = spawn_at(&mut world, 0.0)?;
+        let child = spawn_at(&mut world, 0.0)?;
+        let grandchild = spawn_at(&mut world, 0.0)?;
+        let sibling = spawn_at(&mut world, 0.0)?;
+        world.set_parent(child, root)?;
+        world.set_parent(grandchild, child)?;
+        world.set_parent(sibling, root)?;
+
+        world.despawn_recursive(child);
+        assert!(!world.is_alive(child));
+        assert!(!world.is_alive(grandchild));
+        assert_eq!(world.get_component(root), Some(&Children(vec![sibling])));
+
+        world.despawn_recursive(root);
+        assert!(world.entities().is_empty());
+
+        Ok(())
+    }
+}
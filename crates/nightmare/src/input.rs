@@ -0,0 +1,815 @@
+use crate::{
+    app::{Io, Mouse},
+    Duration, Instant,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use winit::keyboard::KeyCode;
+
+/// Something the player can press
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InputSource {
+    Key(KeyCode),
+    MouseButton(winit::event::MouseButton),
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+}
+
+impl InputSource {
+    fn is_pressed(&self, io: &Io) -> bool {
+        match *self {
+            Self::Key(key_code) => io.is_key_pressed(key_code),
+            Self::MouseButton(button) => is_mouse_button_pressed(&io.mouse, button),
+            Self::WheelUp => io.mouse.wheel_delta.y > 0.0,
+            Self::WheelDown => io.mouse.wheel_delta.y < 0.0,
+            Self::WheelLeft => io.mouse.wheel_delta.x < 0.0,
+            Self::WheelRight => io.mouse.wheel_delta.x > 0.0,
+        }
+    }
+}
+
+fn is_mouse_button_pressed(mouse: &Mouse, button: winit::event::MouseButton) -> bool {
+    match button {
+        winit::event::MouseButton::Left => mouse.is_left_clicked,
+        winit::event::MouseButton::Middle => mouse.is_middle_clicked,
+        winit::event::MouseButton::Right => mouse.is_right_clicked,
+        _ => mouse.buttons.pressed(button),
+    }
+}
+
+/// Modifier keys that must be held for a binding to count,
+/// where either the left or right key will do
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Self = Self {
+        shift: false,
+        control: false,
+        alt: false,
+        logo: false,
+    };
+    pub const SHIFT: Self = Self {
+        shift: true,
+        ..Self::NONE
+    };
+    pub const CONTROL: Self = Self {
+        control: true,
+        ..Self::NONE
+    };
+    pub const ALT: Self = Self {
+        alt: true,
+        ..Self::NONE
+    };
+    pub const LOGO: Self = Self {
+        logo: true,
+        ..Self::NONE
+    };
+
+    fn are_held(&self, io: &Io) -> bool {
+        let held = |left, right| io.is_key_pressed(left) || io.is_key_pressed(right);
+        (!self.shift || held(KeyCode::ShiftLeft, KeyCode::ShiftRight))
+            && (!self.control || held(KeyCode::ControlLeft, KeyCode::ControlRight))
+            && (!self.alt || held(KeyCode::AltLeft, KeyCode::AltRight))
+            && (!self.logo || held(KeyCode::SuperLeft, KeyCode::SuperRight))
+    }
+
+    /// Whether every modifier of `other` is also part of these
+    fn contains(&self, other: &Self) -> bool {
+        (self.shift || !other.shift)
+            && (self.control || !other.control)
+            && (self.alt || !other.alt)
+            && (self.logo || !other.logo)
+    }
+}
+
+/// An input plus the modifiers that must be held along with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Binding {
+    pub input: InputSource,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub modifiers: Modifiers,
+}
+
+impl Binding {
+    pub fn new(input: InputSource) -> Self {
+        Self {
+            input,
+            modifiers: Modifiers::NONE,
+        }
+    }
+
+    pub fn with_modifiers(mut self, modifiers: Modifiers) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+
+    /// Whether the input and its modifiers are held,
+    /// regardless of any other bindings that share the input
+    pub fn is_pressed(&self, io: &Io) -> bool {
+        self.input.is_pressed(io) && self.modifiers.are_held(io)
+    }
+}
+
+impl From<InputSource> for Binding {
+    fn from(input: InputSource) -> Self {
+        Self::new(input)
+    }
+}
+
+impl From<KeyCode> for Binding {
+    fn from(key_code: KeyCode) -> Self {
+        Self::new(InputSource::Key(key_code))
+    }
+}
+
+impl From<winit::event::MouseButton> for Binding {
+    fn from(button: winit::event::MouseButton) -> Self {
+        Self::new(InputSource::MouseButton(button))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MouseAxis {
+    X,
+    Y,
+}
+
+/// Drives a value that ranges from -1 to 1 for buttons,
+/// or follows the mouse's per-frame deltas
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AxisBinding {
+    /// -1 while `negative` is held, 1 while `positive` is held
+    /// and 0 while both or neither are
+    Buttons {
+        negative: Binding,
+        positive: Binding,
+    },
+    /// Lines scrolled this frame
+    Wheel(MouseAxis),
+    /// Pixels the cursor moved this frame
+    Motion(MouseAxis),
+}
+
+impl AxisBinding {
+    pub fn buttons(negative: impl Into<Binding>, positive: impl Into<Binding>) -> Self {
+        Self::Buttons {
+            negative: negative.into(),
+            positive: positive.into(),
+        }
+    }
+
+    pub fn value(&self, io: &Io) -> f32 {
+        self.value_with(io, |binding| binding.is_pressed(io))
+    }
+
+    fn value_with(&self, io: &Io, is_pressed: impl Fn(&Binding) -> bool) -> f32 {
+        let component = |vector: nalgebra_glm::Vec2, axis: &MouseAxis| match axis {
+            MouseAxis::X => vector.x,
+            MouseAxis::Y => vector.y,
+        };
+        match self {
+            Self::Buttons { negative, positive } => {
+                is_pressed(positive) as i32 as f32 - is_pressed(negative) as i32 as f32
+            }
+            Self::Wheel(axis) => component(io.mouse.wheel_delta, axis),
+            Self::Motion(axis) => component(io.mouse.position_delta, axis),
+        }
+    }
+}
+
+/// Drives a 2D value from a pair of axes
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DualAxisBinding {
+    pub x: AxisBinding,
+    pub y: AxisBinding,
+}
+
+impl DualAxisBinding {
+    /// Four buttons such as WASD, with up as positive y
+    pub fn buttons(
+        up: impl Into<Binding>,
+        down: impl Into<Binding>,
+        left: impl Into<Binding>,
+        right: impl Into<Binding>,
+    ) -> Self {
+        Self {
+            x: AxisBinding::buttons(left, right),
+            y: AxisBinding::buttons(down, up),
+        }
+    }
+
+    /// The arrow keys, or W, A, S and D when `wasd` is set
+    pub fn arrow_keys(wasd: bool) -> Self {
+        if wasd {
+            Self::buttons(KeyCode::KeyW, KeyCode::KeyS, KeyCode::KeyA, KeyCode::KeyD)
+        } else {
+            Self::buttons(
+                KeyCode::ArrowUp,
+                KeyCode::ArrowDown,
+                KeyCode::ArrowLeft,
+                KeyCode::ArrowRight,
+            )
+        }
+    }
+
+    /// Button composites are kept within the unit circle,
+    /// so moving diagonally is no faster than moving straight
+    pub fn value(&self, io: &Io) -> nalgebra_glm::Vec2 {
+        self.value_with(io, |binding| binding.is_pressed(io))
+    }
+
+    fn value_with(&self, io: &Io, is_pressed: impl Fn(&Binding) -> bool) -> nalgebra_glm::Vec2 {
+        let value = nalgebra_glm::vec2(
+            self.x.value_with(io, &is_pressed),
+            self.y.value_with(io, &is_pressed),
+        );
+        match (self.x, self.y) {
+            (AxisBinding::Buttons { .. }, AxisBinding::Buttons { .. }) if value.norm() > 1.0 => {
+                value.normalize()
+            }
+            _ => value,
+        }
+    }
+}
+
+/// Named actions and axes bound to inputs, so controls can be rebound
+/// without touching gameplay code.
+///
+/// When several bindings drive the same action, any of them will press it,
+/// and axes take the value of the binding with the largest magnitude.
+/// Bindings that share an input are exclusive: while one with more modifiers is held,
+/// those with fewer are not, so Ctrl+S doesn't also press S.
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct InputMap {
+    pub actions: BTreeMap<String, Vec<Binding>>,
+    pub axes: BTreeMap<String, Vec<AxisBinding>>,
+    pub dual_axes: BTreeMap<String, Vec<DualAxisBinding>>,
+}
+
+impl InputMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind_action(
+        &mut self,
+        name: impl Into<String>,
+        binding: impl Into<Binding>,
+    ) -> &mut Self {
+        self.actions
+            .entry(name.into())
+            .or_default()
+            .push(binding.into());
+        self
+    }
+
+    pub fn bind_axis(&mut self, name: impl Into<String>, binding: AxisBinding) -> &mut Self {
+        self.axes.entry(name.into()).or_default().push(binding);
+        self
+    }
+
+    pub fn bind_dual_axis(
+        &mut self,
+        name: impl Into<String>,
+        binding: DualAxisBinding,
+    ) -> &mut Self {
+        self.dual_axes.entry(name.into()).or_default().push(binding);
+        self
+    }
+
+    /// Removes every binding of an action or axis
+    pub fn unbind(&mut self, name: &str) {
+        self.actions.remove(name);
+        self.axes.remove(name);
+        self.dual_axes.remove(name);
+    }
+
+    /// Whether any binding of the action is pressed, false for unknown actions
+    pub fn is_pressed(&self, io: &Io, action: &str) -> bool {
+        self.actions.get(action).is_some_and(|bindings| {
+            bindings
+                .iter()
+                .any(|binding| self.is_binding_pressed(io, binding))
+        })
+    }
+
+    /// Whether a binding is pressed and no binding of its input with more modifiers is
+    fn is_binding_pressed(&self, io: &Io, binding: &Binding) -> bool {
+        binding.is_pressed(io)
+            && !self.bindings().any(|other| {
+                other.input == binding.input
+                    && other.modifiers != binding.modifiers
+                    && other.modifiers.contains(&binding.modifiers)
+                    && other.is_pressed(io)
+            })
+    }
+
+    /// Every button binding of every action and axis
+    fn bindings(&self) -> impl Iterator<Item = &Binding> {
+        let axes = self.axes.values().flatten().chain(
+            self.dual_axes
+                .values()
+                .flatten()
+                .flat_map(|binding| [&binding.x, &binding.y]),
+        );
+        let buttons = axes.flat_map(|binding| match binding {
+            AxisBinding::Buttons { negative, positive } => [Some(negative), Some(positive)],
+            _ => [None, None],
+        });
+        self.actions.values().flatten().chain(buttons.flatten())
+    }
+
+    /// The value of an axis, 0 for unknown axes
+    pub fn axis(&self, io: &Io, axis: &str) -> f32 {
+        self.axes
+            .get(axis)
+            .into_iter()
+            .flatten()
+            .map(|binding| binding.value_with(io, |button| self.is_binding_pressed(io, button)))
+            .fold(0.0, |strongest, value| {
+                if value.abs() > strongest.abs() {
+                    value
+                } else {
+                    strongest
+                }
+            })
+    }
+
+    /// The value of a 2D axis, zero for unknown axes
+    pub fn dual_axis(&self, io: &Io, axis: &str) -> nalgebra_glm::Vec2 {
+        self.dual_axes
+            .get(axis)
+            .into_iter()
+            .flatten()
+            .map(|binding| binding.value_with(io, |button| self.is_binding_pressed(io, button)))
+            .fold(nalgebra_glm::Vec2::zeros(), |strongest, value| {
+                if value.norm() > strongest.norm() {
+                    value
+                } else {
+                    strongest
+                }
+            })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl InputMap {
+    /// Loads bindings from a `.ron` or `.json` file, chosen by its extension
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> crate::genvec::Result<Self> {
+        let path = path.as_ref();
+        let from_source = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ron") => Self::from_ron,
+            Some("json") => Self::from_json,
+            _ => {
+                return Err(UnknownFormatError {
+                    path: path.to_path_buf(),
+                }
+                .into())
+            }
+        };
+        from_source(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn to_ron(&self) -> crate::genvec::Result<String> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn from_ron(source: &str) -> crate::genvec::Result<Self> {
+        Ok(ron::from_str(source)?)
+    }
+
+    pub fn to_json(&self) -> crate::genvec::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(source: &str) -> crate::genvec::Result<Self> {
+        Ok(serde_json::from_str(source)?)
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub struct UnknownFormatError {
+    pub path: std::path::PathBuf,
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for UnknownFormatError {}
+
+#[cfg(feature = "serde")]
+impl std::fmt::Display for UnknownFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "'{}' is neither a '.ron' nor a '.json' file.",
+            self.path.display()
+        )
+    }
+}
+
+/// Tracks which buttons are held and which changed since the last frame.
+///
+/// `press` and `release` are fed with input events, and `new_frame` is called
+/// once per frame before them to clear the `just_` states.
+#[derive(Debug, Clone)]
+pub struct ButtonInput<T: Copy + Eq + std::hash::Hash> {
+    /// The longest gap between two presses that counts as a double press
+    pub double_press_interval: Duration,
+    pressed: HashMap<T, Instant>,
+    just_pressed: HashSet<T>,
+    just_released: HashSet<T>,
+    double_pressed: HashSet<T>,
+    last_press: HashMap<T, Instant>,
+    frame_start: Option<Instant>,
+}
+
+impl<T: Copy + Eq + std::hash::Hash> Default for ButtonInput<T> {
+    fn default() -> Self {
+        Self {
+            double_press_interval: Duration::from_millis(500),
+            pressed: HashMap::new(),
+            just_pressed: HashSet::new(),
+            just_released: HashSet::new(),
+            double_pressed: HashSet::new(),
+            last_press: HashMap::new(),
+            frame_start: None,
+        }
+    }
+}
+
+impl<T: Copy + Eq + std::hash::Hash> ButtonInput<T> {
+    /// Clears the per-frame transitions, with `time` as the start of the frame
+    pub fn new_frame(&mut self, time: Instant) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+        self.double_pressed.clear();
+        self.frame_start = Some(time);
+    }
+
+    /// Presses a button at `time`, ignoring key repeats while it is held
+    pub fn press(&mut self, input: T, time: Instant) {
+        if self.pressed.contains_key(&input) {
+            return;
+        }
+        self.pressed.insert(input, time);
+        self.just_pressed.insert(input);
+
+        let is_double_press = self.last_press.get(&input).is_some_and(|last_press| {
+            time.saturating_duration_since(*last_press) <= self.double_press_interval
+        });
+        if is_double_press {
+            // A third press starts a new double press instead of completing another
+            self.double_pressed.insert(input);
+            self.last_press.remove(&input);
+        } else {
+            self.last_press.insert(input, time);
+        }
+    }
+
+    pub fn release(&mut self, input: T) {
+        if self.pressed.remove(&input).is_some() {
+            self.just_released.insert(input);
+        }
+    }
+
+    /// Releases every button, for example when the window loses focus
+    pub fn release_all(&mut self) {
+        self.just_released
+            .extend(self.pressed.drain().map(|(input, _)| input));
+    }
+
+    pub fn pressed(&self, input: T) -> bool {
+        self.pressed.contains_key(&input)
+    }
+
+    /// Whether the button went down this frame
+    pub fn just_pressed(&self, input: T) -> bool {
+        self.just_pressed.contains(&input)
+    }
+
+    /// Whether the button went up this frame
+    pub fn just_released(&self, input: T) -> bool {
+        self.just_released.contains(&input)
+    }
+
+    /// Whether the button went down this frame shortly after a previous press
+    pub fn double_pressed(&self, input: T) -> bool {
+        self.double_pressed.contains(&input)
+    }
+
+    /// How long the button has been held as of the start of this frame,
+    /// or `None` if it is not held
+    pub fn press_duration(&self, input: T) -> Option<Duration> {
+        let pressed_at = *self.pressed.get(&input)?;
+        Some(
+            self.frame_start
+                .map(|frame_start| frame_start.saturating_duration_since(pressed_at))
+                .unwrap_or_default(),
+        )
+    }
+
+    pub fn get_pressed(&self) -> impl Iterator<Item = &T> {
+        self.pressed.keys()
+    }
+
+    pub fn get_just_pressed(&self) -> impl Iterator<Item = &T> {
+        self.just_pressed.iter()
+    }
+
+    pub fn get_just_released(&self) -> impl Iterator<Item = &T> {
+        self.just_released.iter()
+    }
+}
+
+impl Io {
+    /// Whether an action of `Io::input_map` is pressed
+    pub fn is_action_pressed(&self, action: &str) -> bool {
+        self.input_map.is_pressed(self, action)
+    }
+
+    pub fn axis(&self, axis: &str) -> f32 {
+        self.input_map.axis(self, axis)
+    }
+
+    pub fn dual_axis(&self, axis: &str) -> nalgebra_glm::Vec2 {
+        self.input_map.dual_axis(self, axis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(io: &mut Io, key_code: KeyCode) {
+        io.keystates
+            .insert(key_code, winit::event::ElementState::Pressed);
+    }
+
+    fn release(io: &mut Io, key_code: KeyCode) {
+        io.keystates
+            .insert(key_code, winit::event::ElementState::Released);
+    }
+
+    fn controls() -> InputMap {
+        let mut input_map = InputMap::new();
+        input_map
+            .bind_action("jump", KeyCode::Space)
+            .bind_action("jump", winit::event::MouseButton::Right)
+            .bind_action(
+                "save",
+                Binding::from(KeyCode::KeyS).with_modifiers(Modifiers::CONTROL),
+            )
+            .bind_action("zoom_in", InputSource::WheelUp)
+            .bind_axis("move_x", AxisBinding::buttons(KeyCode::KeyA, KeyCode::KeyD))
+            .bind_axis("move_x", AxisBinding::Motion(MouseAxis::X))
+            .bind_dual_axis("move", DualAxisBinding::arrow_keys(true))
+            .bind_dual_axis("move", DualAxisBinding::arrow_keys(false));
+        input_map
+    }
+
+    #[test]
+    fn actions() {
+        let mut io = Io {
+            input_map: controls(),
+            ..Default::default()
+        };
+        assert!(!io.is_action_pressed("jump"));
+        assert!(!io.is_action_pressed("unbound"));
+
+        press(&mut io, KeyCode::Space);
+        assert!(io.is_action_pressed("jump"));
+        release(&mut io, KeyCode::Space);
+        io.mouse.is_right_clicked = true;
+        assert!(io.is_action_pressed("jump"));
+
+        io.mouse.wheel_delta = nalgebra_glm::vec2(0.0, 1.0);
+        assert!(io.is_action_pressed("zoom_in"));
+
+        // Modifiers must be held along with the key
+        press(&mut io, KeyCode::KeyS);
+        assert!(!io.is_action_pressed("save"));
+        press(&mut io, KeyCode::ControlRight);
+        assert!(io.is_action_pressed("save"));
+    }
+
+    #[test]
+    fn bindings_with_more_modifiers_win() {
+        let mut io = Io {
+            input_map: controls(),
+            ..Default::default()
+        };
+        press(&mut io, KeyCode::KeyS);
+        assert_eq!(io.dual_axis("move"), nalgebra_glm::vec2(0.0, -1.0));
+
+        // Saving doesn't also move
+        press(&mut io, KeyCode::ControlLeft);
+        assert!(io.is_action_pressed("save"));
+        assert_eq!(io.dual_axis("move"), nalgebra_glm::Vec2::zeros());
+
+        // Other modifiers don't shadow anything
+        release(&mut io, KeyCode::ControlLeft);
+        press(&mut io, KeyCode::ShiftLeft);
+        assert!(!io.is_action_pressed("save"));
+        assert_eq!(io.dual_axis("move"), nalgebra_glm::vec2(0.0, -1.0));
+    }
+
+    #[test]
+    fn axes() {
+        let mut io = Io {
+            input_map: controls(),
+            ..Default::default()
+        };
+        press(&mut io, KeyCode::KeyD);
+        assert_eq!(io.axis("move_x"), 1.0);
+        press(&mut io, KeyCode::KeyA);
+        assert_eq!(io.axis("move_x"), 0.0);
+
+        // The strongest binding wins
+        io.mouse.position_delta = nalgebra_glm::vec2(-4.0, 0.0);
+        assert_eq!(io.axis("move_x"), -4.0);
+        assert_eq!(io.axis("unbound"), 0.0);
+    }
+
+    #[test]
+    fn composite_axes() {
+        let mut io = Io {
+            input_map: controls(),
+            ..Default::default()
+        };
+        press(&mut io, KeyCode::KeyW);
+        assert_eq!(io.dual_axis("move"), nalgebra_glm::vec2(0.0, 1.0));
+
+        press(&mut io, KeyCode::KeyD);
+        let diagonal = io.dual_axis("move");
+        assert!((diagonal.norm() - 1.0).abs() < 1e-6);
+        assert!(diagonal.x > 0.0 && diagonal.y > 0.0);
+
+        release(&mut io, KeyCode::KeyW);
+        release(&mut io, KeyCode::KeyD);
+        press(&mut io, KeyCode::ArrowLeft);
+        assert_eq!(io.dual_axis("move"), nalgebra_glm::vec2(-1.0, 0.0));
+    }
+
+    #[test]
+    fn button_transitions() {
+        let start = Instant::now();
+        let mut buttons = ButtonInput::default();
+        buttons.new_frame(start);
+        buttons.press(KeyCode::Space, start);
+        assert!(buttons.pressed(KeyCode::Space));
+        assert!(buttons.just_pressed(KeyCode::Space));
+        assert_eq!(buttons.press_duration(KeyCode::Space), Some(Duration::ZERO));
+
+        // Key repeats don't count as new presses
+        buttons.new_frame(start + Duration::from_millis(100));
+        buttons.press(KeyCode::Space, start + Duration::from_millis(100));
+        assert!(!buttons.just_pressed(KeyCode::Space));
+        assert_eq!(
+            buttons.press_duration(KeyCode::Space),
+            Some(Duration::from_millis(100))
+        );
+
+        buttons.release(KeyCode::Space);
+        assert!(buttons.just_released(KeyCode::Space));
+        assert_eq!(buttons.press_duration(KeyCode::Space), None);
+        buttons.new_frame(start + Duration::from_millis(200));
+        assert!(!buttons.just_released(KeyCode::Space));
+        assert!(!buttons.pressed(KeyCode::Space));
+    }
+
+    #[test]
+    fn double_presses() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut buttons = ButtonInput::default();
+        let click = |buttons: &mut ButtonInput<_>, millis| {
+            buttons.new_frame(at(millis));
+            buttons.press(winit::event::MouseButton::Left, at(millis));
+            buttons.release(winit::event::MouseButton::Left);
+            buttons.double_pressed(winit::event::MouseButton::Left)
+        };
+        assert!(!click(&mut buttons, 0));
+        assert!(click(&mut buttons, 200));
+        assert!(!click(&mut buttons, 400));
+        assert!(!click(&mut buttons, 1000));
+        assert!(click(&mut buttons, 1100));
+    }
+
+    #[test]
+    fn io_tracks_transitions_between_frames() {
+        let mut io = Io::default();
+        let now = Instant::now();
+        let window_center = nalgebra_glm::vec2(0.0, 0.0);
+        let new_events = winit::event::Event::<()>::NewEvents(winit::event::StartCause::Poll);
+        let mouse_input = |state| winit::event::Event::<()>::WindowEvent {
+            window_id: unsafe { winit::window::WindowId::dummy() },
+            event: winit::event::WindowEvent::MouseInput {
+                device_id: unsafe { winit::event::DeviceId::dummy() },
+                state,
+                button: winit::event::MouseButton::Back,
+            },
+        };
+
+        io.receive_event_at(&new_events, window_center, now);
+        io.receive_event_at(
+            &mouse_input(winit::event::ElementState::Pressed),
+            window_center,
+            now,
+        );
+        assert!(io
+            .mouse
+            .buttons
+            .just_pressed(winit::event::MouseButton::Back));
+        io.input_map
+            .bind_action("back", winit::event::MouseButton::Back);
+        assert!(io.is_action_pressed("back"));
+
+        io.receive_event_at(&new_events, window_center, now);
+        assert!(!io
+            .mouse
+            .buttons
+            .just_pressed(winit::event::MouseButton::Back));
+        io.receive_event_at(
+            &mouse_input(winit::event::ElementState::Released),
+            window_center,
+            now,
+        );
+        assert!(io
+            .mouse
+            .buttons
+            .just_released(winit::event::MouseButton::Back));
+        assert!(!io.is_action_pressed("back"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn bindings_load_from_config_files() -> crate::genvec::Result<()> {
+        let input_map = controls();
+        assert_eq!(InputMap::from_ron(&input_map.to_ron()?)?, input_map);
+        assert_eq!(InputMap::from_json(&input_map.to_json()?)?, input_map);
+
+        let input_map = InputMap::from_ron(
+            r#"(
+                actions: {
+                    "jump": [(input: Key(Space))],
+                    "sprint": [(input: Key(KeyW), modifiers: (shift: true))],
+                },
+            )"#,
+        )?;
+        let mut io = Io {
+            input_map,
+            ..Default::default()
+        };
+        press(&mut io, KeyCode::KeyW);
+        assert!(!io.is_action_pressed("sprint"));
+        press(&mut io, KeyCode::ShiftLeft);
+        assert!(io.is_action_pressed("sprint"));
+
+        // Named after the test and process, so concurrent runs never share a file
+        let base = std::env::temp_dir().join(format!(
+            "nightmare_bindings_load_from_config_files_{}",
+            std::process::id()
+        ));
+
+        let path = base.with_extension("json");
+        std::fs::write(&path, controls().to_json()?)?;
+        assert_eq!(InputMap::from_file(&path)?, controls());
+        std::fs::remove_file(path)?;
+
+        let path = base.with_extension("ron");
+        std::fs::write(&path, controls().to_ron()?)?;
+        assert_eq!(InputMap::from_file(&path)?, controls());
+
+        // Unknown extensions are rejected rather than guessed
+        let toml = path.with_extension("toml");
+        std::fs::rename(&path, &toml)?;
+        assert!(InputMap::from_file(&toml).is_err());
+        std::fs::remove_file(toml)?;
+        Ok(())
+    }
+}
@@ -1,14 +1,35 @@
 mod app;
+mod command;
+mod event;
+mod gamepad;
 mod genvec;
 mod graphics;
+mod headless;
+mod hierarchy;
+mod input;
+#[cfg(feature = "serde")]
+mod scene;
+mod schedule;
+mod text;
+mod time;
 mod world;
 
 pub mod prelude {
-    pub use crate::{app::*, Duration, Instant};
+    pub use crate::genvec::{
+        ComponentTicks, GenerationalVec, Handle, HandleAllocator, HandleReserver, Slot, SlotVec,
+        SparseSet, Storage, TypedArena, TypedHandle, CHECK_TICK_THRESHOLD, MAX_CHANGE_AGE,
+    };
+    pub use crate::{
+        app::*, command::*, event::*, gamepad::*, headless::*, hierarchy::*, input::*, schedule::*,
+        text::*, time::*, world::*, Duration, Instant,
+    };
     pub use egui;
     pub use log;
     pub use winit;
 
+    #[cfg(feature = "serde")]
+    pub use crate::scene::*;
+
     #[cfg(target_arch = "wasm32")]
     pub use console_error_panic_hook::set_once as set_panic_hook;
 }
@@ -0,0 +1,444 @@
+use crate::{
+    genvec::Result,
+    hierarchy::{Children, Parent, Transform},
+    world::{Entity, World},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Maps the entities saved in a scene to the entities they were loaded as
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct EntityMap(pub HashMap<Entity, Entity>);
+
+impl EntityMap {
+    /// The loaded entity for a saved entity, if it was part of the scene
+    pub fn get(&self, entity: Entity) -> Option<Entity> {
+        self.0.get(&entity).copied()
+    }
+
+    /// The loaded entity for a saved entity,
+    /// or `Entity::INVALID` if it was not part of the scene,
+    /// so stale references can never point at an unrelated live entity
+    pub fn map(&self, entity: Entity) -> Entity {
+        self.get(entity).unwrap_or(Entity::INVALID)
+    }
+}
+
+/// Implemented by components that store references to other entities,
+/// so those references can be remapped when a scene is loaded
+pub trait MapEntities {
+    fn map_entities(&mut self, entity_map: &EntityMap);
+}
+
+impl MapEntities for Parent {
+    fn map_entities(&mut self, entity_map: &EntityMap) {
+        self.0 = entity_map.map(self.0);
+    }
+}
+
+impl MapEntities for Children {
+    fn map_entities(&mut self, entity_map: &EntityMap) {
+        self.0
+            .iter_mut()
+            .for_each(|child| *child = entity_map.map(*child));
+    }
+}
+
+struct RegisteredComponent {
+    name: String,
+    type_id: std::any::TypeId,
+    save: fn(&World, Entity) -> Option<Result<serde_json::Value>>,
+    load: fn(&mut World, Entity, serde_json::Value, &EntityMap) -> Result<()>,
+}
+
+/// The component types that are written to and read from scenes.
+///
+/// The default registry includes `Transform`, `Parent` and `Children`.
+pub struct SceneRegistry {
+    components: Vec<RegisteredComponent>,
+}
+
+impl Default for SceneRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register::<Transform>("Transform")
+            .register_mapped::<Parent>("Parent")
+            .register_mapped::<Children>("Children");
+        registry
+    }
+}
+
+impl SceneRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry without any of the built in components
+    pub fn empty() -> Self {
+        Self {
+            components: Vec::new(),
+        }
+    }
+
+    /// Registers a component type under a name that is stable across builds
+    pub fn register<T: Serialize + DeserializeOwned + 'static>(
+        &mut self,
+        name: impl Into<String>,
+    ) -> &mut Self {
+        self.components.push(RegisteredComponent {
+            name: name.into(),
+            type_id: std::any::TypeId::of::<T>(),
+            save: save_component::<T>,
+            load: |world, entity, value, _entity_map| {
+                world.add_component(entity, serde_json::from_value::<T>(value)?)
+            },
+        });
+        self
+    }
+
+    /// Registers a component type that references other entities
+    pub fn register_mapped<T: Serialize + DeserializeOwned + MapEntities + 'static>(
+        &mut self,
+        name: impl Into<String>,
+    ) -> &mut Self {
+        self.components.push(RegisteredComponent {
+            name: name.into(),
+            type_id: std::any::TypeId::of::<T>(),
+            save: save_component::<T>,
+            load: |world, entity, value, entity_map| {
+                let mut component = serde_json::from_value::<T>(value)?;
+                component.map_entities(entity_map);
+                world.add_component(entity, component)
+            },
+        });
+        self
+    }
+
+    /// The name a component type is registered under
+    pub(crate) fn name_of<T: 'static>(&self) -> Option<&str> {
+        self.components
+            .iter()
+            .find(|component| component.type_id == std::any::TypeId::of::<T>())
+            .map(|component| component.name.as_str())
+    }
+
+    /// The registered components of an entity, keyed by name
+    pub(crate) fn save(
+        &self,
+        world: &World,
+        entity: Entity,
+    ) -> Result<BTreeMap<String, serde_json::Value>> {
+        self.components
+            .iter()
+            .filter_map(|component| {
+                (component.save)(world, entity)
+                    .map(|value| value.map(|value| (component.name.clone(), value)))
+            })
+            .collect()
+    }
+
+    /// Adds a component by its registered name,
+    /// remapping the entities it references
+    pub(crate) fn load(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        name: &str,
+        value: serde_json::Value,
+        entity_map: &EntityMap,
+    ) -> Result<()> {
+        let component = self
+            .components
+            .iter()
+            .find(|component| component.name == name)
+            .ok_or_else(|| UnregisteredComponentError {
+                name: name.to_string(),
+            })?;
+        (component.load)(world, entity, value, entity_map)
+    }
+}
+
+fn save_component<T: Serialize + 'static>(
+    world: &World,
+    entity: Entity,
+) -> Option<Result<serde_json::Value>> {
+    world
+        .get_component::<T>(entity)
+        .map(|component| serde_json::to_value(component).map_err(Into::into))
+}
+
+#[derive(Debug)]
+pub struct UnregisteredComponentError {
+    pub name: String,
+}
+
+impl std::error::Error for UnregisteredComponentError {}
+
+impl std::fmt::Display for UnregisteredComponentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Component '{}' is not registered.", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct DuplicateEntityError {
+    pub entity: Entity,
+}
+
+impl std::error::Error for DuplicateEntityError {}
+
+impl std::fmt::Display for DuplicateEntityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Entity {:?} appears more than once in the scene.",
+            self.entity
+        )
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneEntity {
+    pub entity: Entity,
+    pub components: BTreeMap<String, serde_json::Value>,
+}
+
+/// A serializable snapshot of the registered components of a world
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub entities: Vec<SceneEntity>,
+}
+
+impl Scene {
+    /// Captures every entity in the world.
+    /// Components that are not registered are skipped.
+    pub fn from_world(world: &World, registry: &SceneRegistry) -> Result<Self> {
+        let entities = world
+            .entities()
+            .into_iter()
+            .map(|entity| {
+                Ok(SceneEntity {
+                    entity,
+                    components: registry.save(world, entity)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { entities })
+    }
+
+    /// Spawns the scene's entities into the world,
+    /// remapping entity references to the newly spawned entities.
+    /// Scenes that list an entity more than once are rejected before anything is spawned.
+    pub fn spawn(&self, world: &mut World, registry: &SceneRegistry) -> Result<EntityMap> {
+        let mut seen = HashSet::new();
+        if let Some(duplicate) = self
+            .entities
+            .iter()
+            .find(|scene_entity| !seen.insert(scene_entity.entity))
+        {
+            return Err(Box::new(DuplicateEntityError {
+                entity: duplicate.entity,
+            }));
+        }
+
+        let entity_map = EntityMap(
+            self.entities
+                .iter()
+                .map(|scene_entity| (scene_entity.entity, world.spawn()))
+                .collect(),
+        );
+
+        let loaded = self.entities.iter().try_for_each(|scene_entity| {
+            let entity = entity_map.map(scene_entity.entity);
+            scene_entity
+                .components
+                .iter()
+                .try_for_each(|(name, value)| {
+                    registry.load(world, entity, name, value.clone(), &entity_map)
+                })
+        });
+
+        // A scene that fails to load leaves nothing behind
+        if let Err(error) = loaded {
+            for entity in entity_map.0.values() {
+                world.despawn(*entity);
+            }
+            return Err(error);
+        }
+        Ok(entity_map)
+    }
+
+    pub fn to_ron(&self) -> Result<String> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn from_ron(source: &str) -> Result<Self> {
+        Ok(ron::from_str(source)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(source: &str) -> Result<Self> {
+        Ok(serde_json::from_str(source)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Target(Entity);
+
+    impl MapEntities for Target {
+        fn map_entities(&mut self, entity_map: &EntityMap) {
+            self.0 = entity_map.map(self.0);
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Team {
+        Red,
+        Blue,
+    }
+
+    fn registry() -> SceneRegistry {
+        let mut registry = SceneRegistry::new();
+        registry
+            .register::<Team>("Team")
+            .register_mapped::<Target>("Target");
+        registry
+    }
+
+    fn level() -> Result<World> {
+        let mut world = World::new();
+        let player = world.spawn();
+        world.add_component(
+            player,
+            Transform::from_translation(nalgebra_glm::vec3(1.0, 2.0, 3.0)),
+        )?;
+        world.add_component(player, Team::Blue)?;
+
+        let weapon = world.spawn();
+        world.add_component(weapon, Transform::default())?;
+        world.set_parent(weapon, player)?;
+
+        let enemy = world.spawn();
+        world.add_component(enemy, Team::Red)?;
+        world.add_component(enemy, Target(player))?;
+        Ok(world)
+    }
+
+    fn assert_level_loaded(world: &World, entity_map: &EntityMap, scene: &Scene) {
+        let [player, weapon, enemy] =
+            [0, 1, 2].map(|index| entity_map.map(scene.entities[index].entity));
+        assert_eq!(
+            world.get_component::<Transform>(player),
+            Some(&Transform::from_translation(nalgebra_glm::vec3(
+                1.0, 2.0, 3.0
+            )))
+        );
+        assert_eq!(world.get_component(player), Some(&Team::Blue));
+        assert_eq!(world.get_component(player), Some(&Children(vec![weapon])));
+        assert_eq!(world.get_component(weapon), Some(&Parent(player)));
+        assert_eq!(world.get_component(enemy), Some(&Target(player)));
+        assert_eq!(world.get_component(enemy), Some(&Team::Red));
+    }
+
+    #[test]
+    fn ron_round_trip() -> Result<()> {
+        let registry = registry();
+        let scene = Scene::from_world(&level()?, &registry)?;
+        let loaded = Scene::from_ron(&scene.to_ron()?)?;
+        assert_eq!(loaded, scene);
+        Ok(())
+    }
+
+    #[test]
+    fn json_round_trip() -> Result<()> {
+        let registry = registry();
+        let scene = Scene::from_world(&level()?, &registry)?;
+        let loaded = Scene::from_json(&scene.to_json()?)?;
+        assert_eq!(loaded, scene);
+        Ok(())
+    }
+
+    #[test]
+    fn entity_references_are_remapped() -> Result<()> {
+        let registry = registry();
+        let scene = Scene::from_world(&level()?, &registry)?;
+
+        // Occupy some slots and bump generations so the loaded handles differ
+        let mut world = World::new();
+        let recycled = world.spawn();
+        world.despawn(recycled);
+        world.spawn();
+        world.spawn();
+
+        let entity_map = scene.spawn(&mut world, &registry)?;
+        for scene_entity in scene.entities.iter() {
+            assert_ne!(
+                entity_map.get(scene_entity.entity),
+                Some(scene_entity.entity)
+            );
+        }
+        assert_level_loaded(&world, &entity_map, &scene);
+
+        Ok(())
+    }
+
+    #[test]
+    fn references_outside_the_scene_are_invalidated() -> Result<()> {
+        // The target is not saved with the scene
+        let mut source = World::new();
+        let outsider = source.spawn();
+        let enemy = source.spawn();
+        source.add_component(enemy, Target(outsider))?;
+        let mut scene = Scene::from_world(&source, &registry())?;
+        scene
+            .entities
+            .retain(|scene_entity| scene_entity.entity == enemy);
+
+        // A live entity occupies the outsider's handle in the new world
+        let mut world = World::new();
+        let bystander = world.spawn();
+        assert_eq!(bystander, outsider);
+
+        let entity_map = scene.spawn(&mut world, &registry())?;
+        assert_eq!(entity_map.get(outsider), None);
+        let enemy = entity_map.map(enemy);
+        assert_eq!(world.get_component(enemy), Some(&Target(Entity::INVALID)));
+        assert!(!world.is_alive(Entity::INVALID));
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_entities_are_rejected() -> Result<()> {
+        let registry = registry();
+        let mut scene = Scene::from_world(&level()?, &registry)?;
+        scene.entities.push(scene.entities[0].clone());
+
+        let mut world = World::new();
+        assert!(scene.spawn(&mut world, &registry).is_err());
+        assert!(world.entities().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn unregistered_components_fail_to_load() -> Result<()> {
+        let scene = Scene::from_world(&level()?, &registry())?;
+        let mut world = World::new();
+        assert!(scene.spawn(&mut world, &SceneRegistry::default()).is_err());
+
+        // Entities spawned before the failure are despawned again
+        assert!(world.entities().is_empty());
+        assert_eq!(world.query::<&Transform>().count(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,775 @@
+use crate::{
+    genvec::Result,
+    world::{Query, QueryFilter, QueryIter, ReadOnlyQuery, World},
+};
+use std::{any::TypeId, collections::HashMap, marker::PhantomData};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Stage {
+    /// Runs once per fixed timestep rather than once per frame,
+    /// so it is not part of `Stage::ALL`
+    FixedUpdate,
+    PreUpdate,
+    Update,
+    PostUpdate,
+    RenderPrep,
+}
+
+impl Stage {
+    /// Every stage in the order it runs each frame
+    pub const ALL: [Stage; 4] = [
+        Stage::PreUpdate,
+        Stage::Update,
+        Stage::PostUpdate,
+        Stage::RenderPrep,
+    ];
+}
+
+#[derive(Debug)]
+pub struct SystemCycleError {
+    pub stage: Stage,
+    pub systems: Vec<String>,
+}
+
+impl std::error::Error for SystemCycleError {}
+
+impl std::fmt::Display for SystemCycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Systems {:?} in stage '{:?}' have cyclic ordering constraints.",
+            self.systems, self.stage
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownSystemError {
+    pub stage: Stage,
+    pub system: String,
+    pub name: String,
+}
+
+impl std::error::Error for UnknownSystemError {}
+
+impl std::fmt::Display for UnknownSystemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "System '{}' is ordered relative to '{}', which is not in stage '{:?}'.",
+            self.system, self.name, self.stage
+        )
+    }
+}
+
+pub struct System {
+    name: String,
+    before: Vec<String>,
+    after: Vec<String>,
+    last_run: u32,
+    has_run: bool,
+    run: Box<dyn FnMut(&mut World)>,
+}
+
+impl System {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The world change tick right after this system last finished,
+    /// so it only sees writes made since then
+    pub fn last_run(&self) -> u32 {
+        self.last_run
+    }
+
+    /// Runs this system before the named system in the same stage
+    pub fn before(&mut self, name: impl Into<String>) -> &mut Self {
+        self.before.push(name.into());
+        self
+    }
+
+    /// Runs this system after the named system in the same stage
+    pub fn after(&mut self, name: impl Into<String>) -> &mut Self {
+        self.after.push(name.into());
+        self
+    }
+}
+
+/// Turns a function into a system the schedule can run
+pub trait IntoSystem<Marker> {
+    fn into_system(self) -> Box<dyn FnMut(&mut World)>;
+}
+
+impl<F: FnMut(&mut World) + 'static> IntoSystem<fn(&mut World)> for F {
+    fn into_system(self) -> Box<dyn FnMut(&mut World)> {
+        Box::new(self)
+    }
+}
+
+/// A system function argument that is fetched from the world every time the system runs
+///
+/// # Safety
+///
+/// `access` must record every component and resource type `fetch` reads
+/// and whether it writes to them.
+pub unsafe trait SystemParam {
+    type Item<'w>;
+
+    fn access(components: &mut Vec<(TypeId, bool)>, resources: &mut Vec<(TypeId, bool)>);
+
+    /// # Safety
+    ///
+    /// The world must outlive `'w`, and nothing else may access
+    /// the types recorded by `access` while the item is alive.
+    unsafe fn fetch<'w>(world: *mut World) -> Self::Item<'w>;
+}
+
+/// Shared access to a resource, which must be in the world when the system runs
+pub struct Res<'w, T: 'static> {
+    value: &'w T,
+}
+
+impl<T> std::ops::Deref for Res<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+unsafe impl<T: 'static> SystemParam for Res<'_, T> {
+    type Item<'w> = Res<'w, T>;
+
+    fn access(_components: &mut Vec<(TypeId, bool)>, resources: &mut Vec<(TypeId, bool)>) {
+        resources.push((TypeId::of::<T>(), false));
+    }
+
+    unsafe fn fetch<'w>(world: *mut World) -> Self::Item<'w> {
+        Option::<Res<T>>::fetch(world).unwrap_or_else(|| missing_resource::<T>())
+    }
+}
+
+unsafe impl<T: 'static> SystemParam for Option<Res<'_, T>> {
+    type Item<'w> = Option<Res<'w, T>>;
+
+    fn access(components: &mut Vec<(TypeId, bool)>, resources: &mut Vec<(TypeId, bool)>) {
+        Res::<T>::access(components, resources);
+    }
+
+    unsafe fn fetch<'w>(world: *mut World) -> Self::Item<'w> {
+        (*world).resource::<T>().map(|value| Res { value })
+    }
+}
+
+/// Exclusive access to a resource, which must be in the world when the system runs
+pub struct ResMut<'w, T: 'static> {
+    value: &'w mut T,
+}
+
+impl<T> std::ops::Deref for ResMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<T> std::ops::DerefMut for ResMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+unsafe impl<T: 'static> SystemParam for ResMut<'_, T> {
+    type Item<'w> = ResMut<'w, T>;
+
+    fn access(_components: &mut Vec<(TypeId, bool)>, resources: &mut Vec<(TypeId, bool)>) {
+        resources.push((TypeId::of::<T>(), true));
+    }
+
+    unsafe fn fetch<'w>(world: *mut World) -> Self::Item<'w> {
+        Option::<ResMut<T>>::fetch(world).unwrap_or_else(|| missing_resource::<T>())
+    }
+}
+
+unsafe impl<T: 'static> SystemParam for Option<ResMut<'_, T>> {
+    type Item<'w> = Option<ResMut<'w, T>>;
+
+    fn access(components: &mut Vec<(TypeId, bool)>, resources: &mut Vec<(TypeId, bool)>) {
+        ResMut::<T>::access(components, resources);
+    }
+
+    unsafe fn fetch<'w>(world: *mut World) -> Self::Item<'w> {
+        (*world).resource_mut::<T>().map(|value| ResMut { value })
+    }
+}
+
+fn missing_resource<T>() -> ! {
+    panic!(
+        "Resource {} is missing from the world!",
+        std::any::type_name::<T>()
+    )
+}
+
+/// Iterates over the entities that match a query from within a system
+pub struct SystemQuery<'w, Q: Query, F: QueryFilter = ()> {
+    world: *mut World,
+    _query: PhantomData<(&'w mut World, Q, F)>,
+}
+
+impl<Q: Query, F: QueryFilter> SystemQuery<'_, Q, F> {
+    pub fn iter_mut(&mut self) -> QueryIter<'_, Q, F> {
+        // Safety: the system's params were checked for aliasing when it was added,
+        // so no other param can reach the components this query accesses
+        unsafe { (*self.world).query_filtered::<Q, F>() }
+    }
+}
+
+impl<Q: ReadOnlyQuery, F: QueryFilter> SystemQuery<'_, Q, F> {
+    pub fn iter(&self) -> QueryIter<'_, Q, F> {
+        unsafe { (*self.world).query_filtered_ref::<Q, F>() }
+    }
+}
+
+impl<'a, Q: Query, F: QueryFilter> IntoIterator for &'a mut SystemQuery<'_, Q, F> {
+    type Item = Q::Item<'a>;
+    type IntoIter = QueryIter<'a, Q, F>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+unsafe impl<Q: Query, F: QueryFilter> SystemParam for SystemQuery<'_, Q, F> {
+    type Item<'w> = SystemQuery<'w, Q, F>;
+
+    fn access(components: &mut Vec<(TypeId, bool)>, _resources: &mut Vec<(TypeId, bool)>) {
+        Q::access(components);
+    }
+
+    unsafe fn fetch<'w>(world: *mut World) -> Self::Item<'w> {
+        SystemQuery {
+            world,
+            _query: PhantomData,
+        }
+    }
+}
+
+macro_rules! impl_into_system {
+    ($($param:ident),+) => {
+        impl<Func, $($param: SystemParam),+> IntoSystem<fn($($param,)+)> for Func
+        where
+            Func: 'static,
+            for<'a> &'a mut Func: FnMut($($param),+) + FnMut($($param::Item<'_>),+),
+        {
+            #[allow(non_snake_case)]
+            fn into_system(mut self) -> Box<dyn FnMut(&mut World)> {
+                let (mut components, mut resources) = (Vec::new(), Vec::new());
+                $($param::access(&mut components, &mut resources);)+
+                assert!(
+                    !crate::world::is_aliased(&components) && !crate::world::is_aliased(&resources),
+                    "System {} accesses a component or resource mutably more than once!",
+                    std::any::type_name::<Func>()
+                );
+
+                // Calling through a generic function picks the `FnMut` impl taking the items
+                #[allow(clippy::too_many_arguments)]
+                fn call<$($param),+>(mut run: impl FnMut($($param),+), $($param: $param),+) {
+                    run($($param),+)
+                }
+                Box::new(move |world: &mut World| {
+                    let world: *mut World = world;
+                    // Safety: the world is borrowed for the whole call and the params
+                    // were checked for aliasing above
+                    unsafe { call(&mut self, $($param::fetch(world)),+) }
+                })
+            }
+        }
+    };
+}
+
+impl_into_system!(A);
+impl_into_system!(A, B);
+impl_into_system!(A, B, C);
+impl_into_system!(A, B, C, D);
+impl_into_system!(A, B, C, D, E);
+impl_into_system!(A, B, C, D, E, F);
+impl_into_system!(A, B, C, D, E, F, G);
+impl_into_system!(A, B, C, D, E, F, G, H);
+
+/// Systems grouped into stages, run once per frame by the app runtime.
+///
+/// Within a stage systems run in registration order
+/// unless `before`/`after` constraints say otherwise.
+#[derive(Default)]
+pub struct Schedule {
+    stages: HashMap<Stage, Vec<System>>,
+    /// The order of every stage built since its systems last changed,
+    /// or `None` if its constraints are invalid
+    orders: HashMap<Stage, Option<Vec<usize>>>,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a system, which is either a function taking `&mut World`
+    /// or a function taking system params such as `Res`, `ResMut` and `SystemQuery`
+    pub fn add_system<Marker>(
+        &mut self,
+        stage: Stage,
+        name: impl Into<String>,
+        system: impl IntoSystem<Marker>,
+    ) -> &mut System {
+        self.orders.remove(&stage);
+        let systems = self.stages.entry(stage).or_default();
+        systems.push(System {
+            name: name.into(),
+            before: Vec::new(),
+            after: Vec::new(),
+            last_run: 0,
+            has_run: false,
+            run: system.into_system(),
+        });
+        systems.last_mut().expect("A system was just added!")
+    }
+
+    /// Checks the ordering constraints of every stage that changed since it was
+    /// last built and caches the order its systems run in.
+    /// A stage with unknown names or cycles in its constraints is reported once
+    /// and then skipped until another system is added to it.
+    pub fn build(&mut self) -> Result<()> {
+        let stages = self.stages.keys().copied().collect::<Vec<_>>();
+        let mut result = Ok(());
+        for stage in stages {
+            if let Err(error) = self.build_stage(stage) {
+                if result.is_ok() {
+                    result = Err(error);
+                }
+            }
+        }
+        result
+    }
+
+    fn build_stage(&mut self, stage: Stage) -> Result<()> {
+        if self.orders.contains_key(&stage) {
+            return Ok(());
+        }
+        let order = self.order(stage);
+        self.orders.insert(stage, order.as_ref().ok().cloned());
+        order.map(drop)
+    }
+
+    /// Runs every stage in order, building the schedule first.
+    /// Stages that fail to build are skipped and the first error is returned.
+    pub fn run(&mut self, world: &mut World) -> Result<()> {
+        let result = self.build();
+        for stage in Stage::ALL {
+            self.run_stage(stage, world)?;
+        }
+
+        // Like component ticks, last runs must not get old enough to wrap around
+        let change_tick = world.change_tick();
+        self.stages
+            .values_mut()
+            .flatten()
+            .for_each(|system| crate::genvec::clamp_tick(&mut system.last_run, change_tick));
+
+        // Removals older than the oldest last run have been seen by every system.
+        // Systems that never ran, like fixed systems before the first fixed step,
+        // would hold on to every removal, so they are left out.
+        let oldest_run = self
+            .stages
+            .values()
+            .flatten()
+            .filter(|system| system.has_run)
+            .map(System::last_run)
+            .max_by_key(|last_run| change_tick.wrapping_sub(*last_run))
+            .unwrap_or(change_tick);
+        world.clear_removed_components(oldest_run);
+        result
+    }
+
+    /// Runs a single stage, building it first if its systems changed
+    pub fn run_stage(&mut self, stage: Stage, world: &mut World) -> Result<()> {
+        self.build_stage(stage)?;
+        let (Some(Some(order)), Some(systems)) =
+            (self.orders.get(&stage), self.stages.get_mut(&stage))
+        else {
+            return Ok(());
+        };
+        for &index in order {
+            let system = &mut systems[index];
+            world.set_last_run_tick(system.last_run);
+            (system.run)(world);
+            system.last_run = world.increment_change_tick();
+            system.has_run = true;
+        }
+        Ok(())
+    }
+
+    /// The names of the systems in a stage in the order they will run
+    pub fn system_order(&self, stage: Stage) -> Result<Vec<&str>> {
+        let Some(systems) = self.stages.get(&stage) else {
+            return Ok(Vec::new());
+        };
+        Ok(self
+            .order(stage)?
+            .into_iter()
+            .map(|index| systems[index].name())
+            .collect())
+    }
+
+    /// Topologically sorts a stage, preferring registration order
+    /// whenever the constraints leave a choice
+    fn order(&self, stage: Stage) -> Result<Vec<usize>> {
+        let Some(systems) = self.stages.get(&stage) else {
+            return Ok(Vec::new());
+        };
+
+        for system in systems {
+            let unknown = system
+                .before
+                .iter()
+                .chain(system.after.iter())
+                .find(|name| systems.iter().all(|other| other.name != **name));
+            if let Some(name) = unknown {
+                return Err(Box::new(UnknownSystemError {
+                    stage,
+                    system: system.name.clone(),
+                    name: name.clone(),
+                }));
+            }
+        }
+
+        let mut dependencies = vec![Vec::new(); systems.len()];
+        for (index, system) in systems.iter().enumerate() {
+            for (other_index, other) in systems.iter().enumerate() {
+                if system.after.contains(&other.name) || other.before.contains(&system.name) {
+                    dependencies[index].push(other_index);
+                }
+            }
+        }
+
+        let mut order = Vec::with_capacity(systems.len());
+        let mut scheduled = vec![false; systems.len()];
+        while order.len() < systems.len() {
+            let next = (0..systems.len()).find(|index| {
+                !scheduled[*index]
+                    && dependencies[*index]
+                        .iter()
+                        .all(|dependency| scheduled[*dependency])
+            });
+            match next {
+                Some(index) => {
+                    scheduled[index] = true;
+                    order.push(index);
+                }
+                None => {
+                    let systems = systems
+                        .iter()
+                        .zip(scheduled)
+                        .filter(|(_, scheduled)| !scheduled)
+                        .map(|(system, _)| system.name.clone())
+                        .collect();
+                    return Err(Box::new(SystemCycleError { stage, systems }));
+                }
+            }
+        }
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Log(Vec<&'static str>);
+
+    fn logger(name: &'static str) -> impl FnMut(&mut World) {
+        move |world: &mut World| {
+            let entity = world.entities()[0];
+            if let Some(log) = world.get_component_mut::<Log>(entity) {
+                log.0.push(name);
+            }
+        }
+    }
+
+    #[test]
+    fn registration_order_is_kept() -> Result<()> {
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, "first", |_: &mut World| {});
+        schedule.add_system(Stage::Update, "second", |_: &mut World| {});
+        schedule.add_system(Stage::Update, "third", |_: &mut World| {});
+        assert_eq!(
+            schedule.system_order(Stage::Update)?,
+            ["first", "second", "third"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn before_and_after_constraints() -> Result<()> {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, "render", |_: &mut World| {})
+            .after("physics");
+        schedule.add_system(Stage::Update, "physics", |_: &mut World| {});
+        schedule
+            .add_system(Stage::Update, "input", |_: &mut World| {})
+            .before("physics");
+        assert_eq!(
+            schedule.system_order(Stage::Update)?,
+            ["input", "physics", "render"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cycles_are_reported() -> Result<()> {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.add_component(entity, Log::default())?;
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, "a", logger("a"))
+            .after("b");
+        schedule
+            .add_system(Stage::Update, "b", logger("b"))
+            .after("a");
+        schedule.add_system(Stage::Update, "c", logger("c"));
+        schedule.add_system(Stage::PostUpdate, "d", logger("d"));
+        assert!(schedule.system_order(Stage::Update).is_err());
+        assert!(schedule.build().is_err());
+
+        // The broken stage is reported once and skipped, while the others keep running
+        assert!(schedule.run(&mut world).is_ok());
+        assert!(schedule.run(&mut world).is_ok());
+        assert_eq!(world.get_component::<Log>(entity).unwrap().0, ["d", "d"]);
+
+        // Adding a system rebuilds the stage
+        schedule.add_system(Stage::Update, "e", logger("e"));
+        assert!(schedule.run(&mut world).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_names_are_reported() -> Result<()> {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, "a", |_: &mut World| {})
+            .after("missing");
+        schedule
+            .add_system(Stage::PostUpdate, "b", |_: &mut World| {})
+            .before("a");
+        assert!(schedule.build().is_err());
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, "a", |_: &mut World| {});
+        schedule
+            .add_system(Stage::Update, "b", |_: &mut World| {})
+            .before("a");
+        schedule.build()?;
+        assert_eq!(schedule.system_order(Stage::Update)?, ["b", "a"]);
+        Ok(())
+    }
+
+    #[test]
+    fn stages_run_in_order() -> Result<()> {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.add_component(entity, Log::default())?;
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::RenderPrep, "render_prep", logger("render_prep"));
+        schedule.add_system(Stage::PostUpdate, "post_update", logger("post_update"));
+        schedule.add_system(Stage::Update, "late", logger("late"));
+        schedule
+            .add_system(Stage::Update, "early", logger("early"))
+            .before("late");
+        schedule.add_system(Stage::PreUpdate, "pre_update", logger("pre_update"));
+        schedule.run(&mut world)?;
+
+        assert_eq!(
+            world.get_component::<Log>(entity).unwrap().0,
+            ["pre_update", "early", "late", "post_update", "render_prep"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn systems_fetch_their_params() -> Result<()> {
+        use crate::world::Changed;
+
+        struct Speed(u32);
+
+        #[derive(Default)]
+        struct Moved(Vec<u32>);
+
+        let mut world = World::new();
+        for position in [0_u32, 10] {
+            let entity = world.spawn();
+            world.add_component(entity, position)?;
+        }
+        world.spawn();
+        world.insert_resource(Speed(2));
+        world.insert_resource(Moved::default());
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(
+            Stage::Update,
+            "move",
+            |mut positions: SystemQuery<&mut u32>, speed: Res<Speed>| {
+                for mut position in &mut positions {
+                    *position += speed.0;
+                }
+            },
+        );
+        schedule.add_system(
+            Stage::PostUpdate,
+            "record",
+            |positions: SystemQuery<&u32, Changed<u32>>,
+             mut moved: ResMut<Moved>,
+             missing: Option<Res<String>>| {
+                assert!(missing.is_none());
+                moved.0.extend(positions.iter().copied());
+            },
+        );
+        schedule.run(&mut world)?;
+        schedule.run(&mut world)?;
+
+        assert_eq!(world.resource::<Moved>().unwrap().0, [2, 12, 4, 14]);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn params_that_alias_are_rejected() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(
+            Stage::Update,
+            "alias",
+            |_: SystemQuery<&mut u32>, _: SystemQuery<&u32>| {},
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn missing_resources_panic() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, "read", |_: Res<String>| {});
+        let _ = schedule.run(&mut World::new());
+    }
+
+    #[test]
+    fn systems_only_see_changes_since_their_last_run() -> Result<()> {
+        use crate::world::{Changed, Entity};
+
+        #[derive(Default)]
+        struct Seen(Vec<usize>);
+
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.add_component(entity, 0_u32)?;
+        world.insert_resource(Seen::default());
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, "count_changed", |world: &mut World| {
+            let changed = world.query_filtered::<Entity, Changed<u32>>().count();
+            if let Some(seen) = world.resource_mut::<Seen>() {
+                seen.0.push(changed);
+            }
+        });
+        schedule.add_system(
+            Stage::PostUpdate,
+            "write_odd_frames",
+            |world: &mut World| {
+                let frame = world.resource::<Seen>().map_or(0, |seen| seen.0.len());
+                if frame % 2 == 1 {
+                    for mut value in world.query::<&mut u32>() {
+                        *value += 1;
+                    }
+                }
+            },
+        );
+
+        for _ in 0..4 {
+            schedule.run(&mut world)?;
+        }
+        // Writes made outside of the schedule are seen as well
+        world.add_component(entity, 10_u32)?;
+        schedule.run(&mut world)?;
+
+        assert_eq!(world.resource::<Seen>().unwrap().0, [1, 1, 0, 1, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn removals_are_seen_once_by_every_system() -> Result<()> {
+        #[derive(Default)]
+        struct Seen(Vec<(&'static str, usize)>);
+
+        fn count_removed(name: &'static str) -> impl FnMut(&mut World) {
+            move |world: &mut World| {
+                let removed = world.removed_components::<u32>().count();
+                if let Some(seen) = world.resource_mut::<Seen>() {
+                    seen.0.push((name, removed));
+                }
+            }
+        }
+
+        let mut world = World::new();
+        let first = world.spawn();
+        let second = world.spawn();
+        world.add_component(first, 0_u32)?;
+        world.add_component(second, 0_u32)?;
+        world.insert_resource(Seen::default());
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::PreUpdate, "early", count_removed("early"));
+        schedule.add_system(Stage::Update, "remove", move |world: &mut World| {
+            world.remove_component::<u32>(first);
+        });
+        schedule.add_system(Stage::PostUpdate, "late", count_removed("late"));
+
+        schedule.run(&mut world)?;
+        world.despawn(second);
+        schedule.run(&mut world)?;
+        schedule.run(&mut world)?;
+
+        assert_eq!(
+            world.resource::<Seen>().unwrap().0,
+            [
+                ("early", 0),
+                ("late", 1),
+                ("early", 2),
+                ("late", 1),
+                ("early", 0),
+                ("late", 0)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn systems_that_never_ran_keep_no_removals() -> Result<()> {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.add_component(entity, 0_u32)?;
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::FixedUpdate, "fixed", |_: &mut World| {});
+        schedule.add_system(Stage::Update, "update", |_: &mut World| {});
+        world.remove_component::<u32>(entity);
+        schedule.run(&mut world)?;
+        schedule.run(&mut world)?;
+
+        // Every removal is still readable from the start of time if it was kept
+        world.set_last_run_tick(0);
+        assert_eq!(world.removed_components::<u32>().count(), 0);
+        Ok(())
+    }
+}
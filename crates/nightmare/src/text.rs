@@ -0,0 +1,330 @@
+use crate::app::Io;
+use std::collections::HashMap;
+
+/// A keyboard event with both the physical key and the key it produces
+/// on the current layout, which `winit::event::KeyEvent` can't be built from
+/// outside of winit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyInput {
+    pub physical_key: winit::keyboard::PhysicalKey,
+    pub logical_key: winit::keyboard::Key,
+    /// The text the key types, if any
+    pub text: Option<winit::keyboard::SmolStr>,
+    pub state: winit::event::ElementState,
+    pub repeat: bool,
+}
+
+impl From<&winit::event::KeyEvent> for KeyInput {
+    fn from(event: &winit::event::KeyEvent) -> Self {
+        Self {
+            physical_key: event.physical_key,
+            logical_key: event.logical_key.clone(),
+            text: event.text.clone(),
+            state: event.state,
+            repeat: event.repeat,
+        }
+    }
+}
+
+/// Input method editor events, used to compose text such as CJK characters
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImeEvent {
+    Enabled,
+    /// The text being composed, which replaces any previous preedit text,
+    /// with the cursor as a byte range within it
+    Preedit {
+        text: String,
+        cursor: Option<(usize, usize)>,
+    },
+    /// Finished text, which is also added to `TextInput::text`
+    Commit(String),
+    Disabled,
+}
+
+impl From<&winit::event::Ime> for ImeEvent {
+    fn from(ime: &winit::event::Ime) -> Self {
+        match ime {
+            winit::event::Ime::Enabled => Self::Enabled,
+            winit::event::Ime::Preedit(text, cursor) => Self::Preedit {
+                text: text.clone(),
+                cursor: *cursor,
+            },
+            winit::event::Ime::Commit(text) => Self::Commit(text.clone()),
+            winit::event::Ime::Disabled => Self::Disabled,
+        }
+    }
+}
+
+/// Typed text and the keys behind it, for chat boxes and name entry
+#[derive(Default, Debug, Clone)]
+pub struct TextInput {
+    text: String,
+    key_events: Vec<KeyInput>,
+    ime_events: Vec<ImeEvent>,
+    preedit: Option<(String, Option<(usize, usize)>)>,
+    logical_keys: HashMap<winit::keyboard::PhysicalKey, winit::keyboard::Key>,
+}
+
+impl TextInput {
+    /// Clears the text and events of the previous frame
+    pub fn new_frame(&mut self) {
+        self.text.clear();
+        self.key_events.clear();
+        self.ime_events.clear();
+    }
+
+    pub fn receive_key_input(&mut self, input: KeyInput) {
+        match input.state {
+            winit::event::ElementState::Pressed => {
+                // Editing keys like backspace and enter type control characters,
+                // which are better read from `key_events`
+                if let Some(text) = &input.text {
+                    self.text
+                        .extend(text.chars().filter(|character| !character.is_control()));
+                }
+                self.logical_keys
+                    .insert(input.physical_key, input.logical_key.clone());
+            }
+            winit::event::ElementState::Released => {
+                self.logical_keys.remove(&input.physical_key);
+            }
+        }
+        self.key_events.push(input);
+    }
+
+    pub fn receive_ime_event(&mut self, event: ImeEvent) {
+        match &event {
+            ImeEvent::Preedit { text, cursor } if !text.is_empty() => {
+                self.preedit = Some((text.clone(), *cursor));
+            }
+            ImeEvent::Preedit { .. } | ImeEvent::Disabled => self.preedit = None,
+            ImeEvent::Commit(text) => {
+                self.preedit = None;
+                self.text.push_str(text);
+            }
+            ImeEvent::Enabled => {}
+        }
+        self.ime_events.push(event);
+    }
+
+    /// Releases every key, for example when the window loses focus
+    pub fn release_all(&mut self) {
+        self.logical_keys.clear();
+    }
+
+    /// The text typed this frame, including text committed by the IME
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The keys pressed and released this frame, in order
+    pub fn key_events(&self) -> &[KeyInput] {
+        &self.key_events
+    }
+
+    /// The IME events received this frame, in order
+    pub fn ime_events(&self) -> &[ImeEvent] {
+        &self.ime_events
+    }
+
+    /// The text being composed in the IME and its cursor,
+    /// which should be drawn at the insertion point until it is committed
+    pub fn preedit(&self) -> Option<(&str, Option<(usize, usize)>)> {
+        self.preedit
+            .as_ref()
+            .map(|(text, cursor)| (text.as_str(), *cursor))
+    }
+
+    /// Whether a held key produces `key` on the current layout
+    pub fn is_logical_key_pressed(&self, key: &winit::keyboard::Key) -> bool {
+        self.logical_keys.values().any(|held| held == key)
+    }
+}
+
+impl Io {
+    /// The text typed this frame, see `TextInput::text`
+    pub fn text(&self) -> &str {
+        self.text_input.text()
+    }
+
+    pub fn is_logical_key_pressed(&self, key: &winit::keyboard::Key) -> bool {
+        self.text_input.is_logical_key_pressed(key)
+    }
+
+    /// Whether a held key types `character` on the current layout,
+    /// such as "z" on any keyboard regardless of where the key sits
+    pub fn is_character_pressed(&self, character: &str) -> bool {
+        self.text_input
+            .logical_keys
+            .values()
+            .any(|held| match held {
+                winit::keyboard::Key::Character(text) => text.eq_ignore_ascii_case(character),
+                _ => false,
+            })
+    }
+}
+
+/// The system clipboard, or a clipboard private to the app
+/// when there is no window or the system clipboard is unavailable
+#[derive(Default)]
+pub struct Clipboard {
+    system: Option<egui_winit::clipboard::Clipboard>,
+    text: String,
+}
+
+impl Clipboard {
+    pub(crate) fn system(
+        display_handle: Option<winit::raw_window_handle::RawDisplayHandle>,
+    ) -> Self {
+        Self {
+            system: Some(egui_winit::clipboard::Clipboard::new(display_handle)),
+            text: String::new(),
+        }
+    }
+
+    pub fn get(&mut self) -> Option<String> {
+        match &mut self.system {
+            Some(clipboard) => clipboard.get(),
+            None => Some(self.text.clone()),
+        }
+    }
+
+    pub fn set(&mut self, text: impl Into<String>) {
+        match &mut self.system {
+            Some(clipboard) => clipboard.set(text.into()),
+            None => self.text = text.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::keyboard::{Key, KeyCode, NamedKey, PhysicalKey, SmolStr};
+
+    fn key(
+        key_code: KeyCode,
+        logical_key: Key,
+        text: Option<&str>,
+        state: winit::event::ElementState,
+    ) -> KeyInput {
+        KeyInput {
+            physical_key: PhysicalKey::Code(key_code),
+            logical_key,
+            text: text.map(SmolStr::new),
+            state,
+            repeat: false,
+        }
+    }
+
+    fn press(key_code: KeyCode, character: &str) -> KeyInput {
+        key(
+            key_code,
+            Key::Character(SmolStr::new(character)),
+            Some(character),
+            winit::event::ElementState::Pressed,
+        )
+    }
+
+    #[test]
+    fn typed_text_is_buffered_per_frame() {
+        let mut io = Io::default();
+        let now = crate::Instant::now();
+        io.receive_key_input_at(press(KeyCode::KeyH, "H"), now);
+        io.receive_key_input_at(press(KeyCode::KeyI, "i"), now);
+        io.receive_key_input_at(
+            key(
+                KeyCode::Backspace,
+                Key::Named(NamedKey::Backspace),
+                Some("\u{8}"),
+                winit::event::ElementState::Pressed,
+            ),
+            now,
+        );
+
+        assert_eq!(io.text(), "Hi");
+        assert_eq!(io.text_input.key_events().len(), 3);
+        assert_eq!(
+            io.text_input.key_events()[2].logical_key,
+            Key::Named(NamedKey::Backspace)
+        );
+        assert!(io.is_key_pressed(KeyCode::Backspace));
+
+        io.receive_event_at(
+            &winit::event::Event::<()>::NewEvents(winit::event::StartCause::Poll),
+            nalgebra_glm::Vec2::zeros(),
+            now,
+        );
+        assert_eq!(io.text(), "");
+        assert!(io.text_input.key_events().is_empty());
+    }
+
+    #[test]
+    fn logical_keys_follow_the_layout() {
+        let mut io = Io::default();
+        let now = crate::Instant::now();
+
+        // The key labelled Y on a US layout types "z" on a German one
+        io.receive_key_input_at(press(KeyCode::KeyY, "z"), now);
+        assert!(io.is_character_pressed("Z"));
+        assert!(io.is_logical_key_pressed(&Key::Character(SmolStr::new("z"))));
+        assert!(!io.is_character_pressed("y"));
+
+        io.receive_key_input_at(
+            key(
+                KeyCode::KeyY,
+                Key::Character(SmolStr::new("z")),
+                None,
+                winit::event::ElementState::Released,
+            ),
+            now,
+        );
+        assert!(!io.is_character_pressed("z"));
+    }
+
+    #[test]
+    fn ime_composition() {
+        let window_event = |ime| winit::event::Event::<()>::WindowEvent {
+            window_id: unsafe { winit::window::WindowId::dummy() },
+            event: winit::event::WindowEvent::Ime(ime),
+        };
+        let center = nalgebra_glm::Vec2::zeros();
+        let mut io = Io::default();
+        let now = crate::Instant::now();
+
+        io.receive_event_at(&window_event(winit::event::Ime::Enabled), center, now);
+        io.receive_event_at(
+            &window_event(winit::event::Ime::Preedit("にほ".into(), Some((6, 6)))),
+            center,
+            now,
+        );
+        assert_eq!(io.text_input.preedit(), Some(("にほ", Some((6, 6)))));
+        assert_eq!(io.text(), "");
+
+        io.receive_event_at(
+            &window_event(winit::event::Ime::Preedit(String::new(), None)),
+            center,
+            now,
+        );
+        io.receive_event_at(
+            &window_event(winit::event::Ime::Commit("日本".into())),
+            center,
+            now,
+        );
+        assert_eq!(io.text_input.preedit(), None);
+        assert_eq!(io.text(), "日本");
+        assert_eq!(io.text_input.ime_events().len(), 4);
+        assert_eq!(
+            io.text_input.ime_events()[3],
+            ImeEvent::Commit("日本".into())
+        );
+    }
+
+    #[test]
+    fn private_clipboard() {
+        let mut clipboard = Clipboard::default();
+        assert_eq!(clipboard.get(), Some(String::new()));
+        clipboard.set("copied");
+        assert_eq!(clipboard.get(), Some("copied".to_string()));
+    }
+}
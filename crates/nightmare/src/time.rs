@@ -0,0 +1,136 @@
+use crate::Duration;
+
+/// Decides how many fixed-rate simulation steps to run each frame,
+/// carrying leftover frame time over to the next frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedTimestep {
+    pub timestep: Duration,
+    /// The most steps run in a single frame.
+    /// Any further time is dropped so slow frames can't snowball.
+    pub max_steps: u32,
+    accumulator: Duration,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(Duration::from_secs_f64(1.0 / 60.0))
+    }
+}
+
+impl FixedTimestep {
+    pub fn new(timestep: Duration) -> Self {
+        Self {
+            timestep,
+            max_steps: 5,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    /// Fails unless `hz` is finite and greater than zero
+    pub fn from_hz(hz: f64) -> Result<Self, InvalidTickRateError> {
+        if !hz.is_finite() || hz <= 0.0 {
+            return Err(InvalidTickRateError { hz });
+        }
+        Ok(Self::new(Duration::from_secs_f64(1.0 / hz)))
+    }
+
+    pub fn hz(&self) -> f64 {
+        1.0 / self.timestep.as_secs_f64()
+    }
+
+    /// Adds a frame's worth of time and returns the number of steps to run
+    pub fn advance(&mut self, delta_time: Duration) -> u32 {
+        self.accumulator += delta_time;
+        if self.timestep.is_zero() {
+            return 0;
+        }
+
+        let mut steps = 0;
+        while self.accumulator >= self.timestep && steps < self.max_steps {
+            self.accumulator -= self.timestep;
+            steps += 1;
+        }
+
+        if self.accumulator >= self.timestep {
+            let remainder = self.accumulator.as_nanos() % self.timestep.as_nanos();
+            self.accumulator = Duration::from_nanos(remainder as u64);
+        }
+        steps
+    }
+
+    /// How far the simulation is into the next step, from 0 to 1,
+    /// for interpolating between the last two simulated states
+    pub fn alpha(&self) -> f32 {
+        if self.timestep.is_zero() {
+            return 0.0;
+        }
+        (self.accumulator.as_secs_f64() / self.timestep.as_secs_f64()) as f32
+    }
+
+    /// The time carried over towards the next step
+    pub fn accumulated(&self) -> Duration {
+        self.accumulator
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidTickRateError {
+    pub hz: f64,
+}
+
+impl std::error::Error for InvalidTickRateError {}
+
+impl std::fmt::Display for InvalidTickRateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "A tick rate of {} Hz is invalid, it must be finite and greater than zero.",
+            self.hz
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_follow_the_tick_rate() {
+        let mut fixed_timestep = FixedTimestep::from_hz(50.0).unwrap();
+        assert_eq!(fixed_timestep.advance(Duration::from_millis(10)), 0);
+        assert!((fixed_timestep.alpha() - 0.5).abs() < 1e-6);
+
+        assert_eq!(fixed_timestep.advance(Duration::from_millis(15)), 1);
+        assert!((fixed_timestep.alpha() - 0.25).abs() < 1e-6);
+
+        assert_eq!(fixed_timestep.advance(Duration::from_millis(45)), 2);
+        assert!((fixed_timestep.alpha() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn steps_are_clamped() {
+        let mut fixed_timestep = FixedTimestep::from_hz(100.0).unwrap();
+        fixed_timestep.max_steps = 4;
+
+        // A long stall runs a bounded number of steps and drops the rest
+        assert_eq!(fixed_timestep.advance(Duration::from_millis(1005)), 4);
+        assert!(fixed_timestep.accumulated() < fixed_timestep.timestep);
+        assert!((fixed_timestep.alpha() - 0.5).abs() < 1e-6);
+        assert_eq!(fixed_timestep.advance(Duration::from_millis(5)), 1);
+    }
+
+    #[test]
+    fn default_is_sixty_hertz() {
+        let fixed_timestep = FixedTimestep::default();
+        assert!((fixed_timestep.hz() - 60.0).abs() < 1e-3);
+        assert_eq!(fixed_timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn invalid_tick_rates_are_rejected() {
+        assert!(FixedTimestep::from_hz(0.0).is_err());
+        assert!(FixedTimestep::from_hz(-30.0).is_err());
+        assert!(FixedTimestep::from_hz(f64::NAN).is_err());
+        assert!(FixedTimestep::from_hz(f64::INFINITY).is_err());
+    }
+}
@@ -0,0 +1,2119 @@
+use crate::genvec::{
+    clamp_tick, error::HandleNotFoundError, ComponentTicks, GenerationalVec, Handle,
+    HandleAllocator, HandleReserver, Result, Storage, CHECK_TICK_THRESHOLD,
+};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    marker::PhantomData,
+    rc::Rc,
+};
+
+pub type Entity = Handle;
+
+#[derive(Default)]
+pub struct World {
+    allocator: HandleAllocator,
+    components: HashMap<TypeId, Box<dyn ComponentStorage>>,
+    resources: HashMap<TypeId, Box<dyn Any>>,
+    change_tick: u32,
+    last_run_tick: u32,
+    hooks: HashMap<TypeId, ComponentHooks>,
+    removed: HashMap<TypeId, Vec<(Entity, u32)>>,
+    /// The change tick at the last `update_removed_components`
+    removed_update_tick: u32,
+    /// The change tick at the last `clamp_change_ticks`
+    last_check_tick: u32,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Panics
+    ///
+    /// Panics once every entity index is in use.
+    pub fn spawn(&mut self) -> Entity {
+        let entity = self.allocator.allocate();
+        assert!(entity != Entity::INVALID, "Every entity index is in use!");
+        entity
+    }
+
+    /// Reserves an entity without exclusive access to the world.
+    /// It is not alive until `flush_reserved_entities` is called.
+    pub fn reserve_entity(&self) -> Entity {
+        self.allocator.reserve()
+    }
+
+    /// A reserver that worker threads can use to reserve entities
+    pub fn entity_reserver(&self) -> HandleReserver {
+        self.allocator.reserver()
+    }
+
+    /// Brings every reserved entity to life
+    pub fn flush_reserved_entities(&mut self) {
+        self.allocator.flush();
+    }
+
+    /// Removes every component attached to the entity and frees its handle.
+    /// Any children are handed over to the entity's parent.
+    pub fn despawn(&mut self, entity: Entity) {
+        if !self.is_alive(entity) {
+            return;
+        }
+        self.detach_from_hierarchy(entity);
+
+        // Hooks may add components while the entity is despawned,
+        // so removal repeats until none are left
+        loop {
+            let type_ids = self
+                .components
+                .iter()
+                .filter(|(_, storage)| storage.contains(entity))
+                .map(|(type_id, _)| *type_id)
+                .collect::<Vec<_>>();
+            if type_ids.is_empty() {
+                break;
+            }
+            for type_id in type_ids.iter() {
+                self.run_hook(*type_id, entity, |hooks| hooks.on_remove.as_ref());
+                self.record_removal(*type_id, entity);
+            }
+            // A hook may have despawned the entity already
+            if !self.is_alive(entity) {
+                return;
+            }
+            for type_id in type_ids {
+                if let Some(storage) = self.components.get_mut(&type_id) {
+                    storage.remove(entity);
+                }
+            }
+        }
+        self.allocator.deallocate(&entity);
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.allocator.is_allocated(&entity)
+    }
+
+    pub fn entities(&self) -> Vec<Entity> {
+        self.allocator.allocated_handles()
+    }
+
+    /// Adds a component, replacing any existing one.
+    /// A replaced component counts as changed but not as added.
+    pub fn add_component<T: 'static>(&mut self, entity: Entity, component: T) -> Result<()> {
+        if !self.is_alive(entity) {
+            return Err(Box::new(HandleNotFoundError { handle: entity }));
+        }
+
+        // The old component is still in place while `on_replace` runs, so it can be read
+        if self.has_component::<T>(entity) {
+            self.run_hook(TypeId::of::<T>(), entity, |hooks| hooks.on_replace.as_ref());
+            if !self.is_alive(entity) {
+                return Err(Box::new(HandleNotFoundError { handle: entity }));
+            }
+        }
+
+        // The hook may have removed the old component, in which case the new one is added
+        let tick = self.change_tick;
+        let storage = self.storage_mut::<T>();
+        let previous_ticks = storage.ticks(entity);
+        let previous = storage.take(entity);
+        if let Err(error) = storage.insert(entity, component) {
+            if let Some(previous) = previous {
+                storage.insert(entity, previous)?;
+                if let Some(ticks) = previous_ticks {
+                    storage.set_ticks(entity, ticks);
+                }
+            }
+            return Err(error);
+        }
+
+        match (previous, previous_ticks) {
+            (Some(_), Some(ticks)) => storage.set_ticks(
+                entity,
+                ComponentTicks {
+                    changed: tick,
+                    ..ticks
+                },
+            ),
+            (Some(_), None) => storage.set_ticks(entity, ComponentTicks::new(tick)),
+            (None, _) => {
+                storage.set_ticks(entity, ComponentTicks::new(tick));
+                self.run_hook(TypeId::of::<T>(), entity, |hooks| hooks.on_add.as_ref());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_component<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        self.storage::<T>()?.get(entity)
+    }
+
+    /// Marks the component as changed, whether or not it is written to
+    pub fn get_component_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        let tick = self.change_tick;
+        let (component, ticks) = self
+            .components
+            .get_mut(&TypeId::of::<T>())?
+            .as_any_mut()
+            .downcast_mut::<Box<dyn Storage<T>>>()?
+            .get_mut_with_ticks(entity)?;
+        if let Some(ticks) = ticks {
+            ticks.changed = tick;
+        }
+        Some(component)
+    }
+
+    /// The tick that component writes are currently stamped with
+    pub fn change_tick(&self) -> u32 {
+        self.change_tick
+    }
+
+    /// Advances the change tick, returning the new tick.
+    /// Old ticks are clamped every `CHECK_TICK_THRESHOLD` ticks.
+    pub fn increment_change_tick(&mut self) -> u32 {
+        self.change_tick = self.change_tick.wrapping_add(1);
+        if self.change_tick.wrapping_sub(self.last_check_tick) >= CHECK_TICK_THRESHOLD {
+            self.clamp_change_ticks();
+        }
+        self.change_tick
+    }
+
+    /// Moves every tick older than `MAX_CHANGE_AGE` forward,
+    /// so ticks that wrap around don't make stale components look changed.
+    /// Systems' last run ticks are clamped by the schedule.
+    pub fn clamp_change_ticks(&mut self) {
+        let this_run = self.change_tick;
+        self.components
+            .values_mut()
+            .for_each(|storage| storage.clamp_ticks(this_run));
+        self.removed
+            .values_mut()
+            .flatten()
+            .for_each(|(_, tick)| clamp_tick(tick, this_run));
+        clamp_tick(&mut self.last_run_tick, this_run);
+        clamp_tick(&mut self.removed_update_tick, this_run);
+        self.last_check_tick = this_run;
+    }
+
+    /// `Added` and `Changed` filters match components written at or after this tick.
+    /// The schedule sets it to the last run of each system before running it.
+    pub fn last_run_tick(&self) -> u32 {
+        self.last_run_tick
+    }
+
+    pub fn set_last_run_tick(&mut self, tick: u32) {
+        self.last_run_tick = tick;
+    }
+
+    pub fn has_component<T: 'static>(&self, entity: Entity) -> bool {
+        self.get_component::<T>(entity).is_some()
+    }
+
+    pub fn remove_component<T: 'static>(&mut self, entity: Entity) {
+        if !self.has_component::<T>(entity) {
+            return;
+        }
+        self.run_hook(TypeId::of::<T>(), entity, |hooks| hooks.on_remove.as_ref());
+        self.record_removal(TypeId::of::<T>(), entity);
+        self.storage_mut::<T>().remove(entity);
+    }
+
+    /// The hooks run when components of type `T` are added, replaced or removed
+    pub fn component_hooks_mut<T: 'static>(&mut self) -> &mut ComponentHooks {
+        self.hooks.entry(TypeId::of::<T>()).or_default()
+    }
+
+    /// The entities that lost a `T` component at or after the last run tick,
+    /// whether it was removed or its entity was despawned
+    pub fn removed_components<T: 'static>(&self) -> RemovedComponents<'_, T> {
+        RemovedComponents {
+            removed: self
+                .removed
+                .get(&TypeId::of::<T>())
+                .map(|removed| removed.as_slice())
+                .unwrap_or_default()
+                .iter(),
+            last_run: self.last_run_tick,
+            this_run: self.change_tick,
+            _component: PhantomData,
+        }
+    }
+
+    /// Forgets the removals recorded before the previous update, so like events,
+    /// removals stay readable for the frame they happen in and the frame after.
+    /// The app runtime calls this once per frame.
+    pub fn update_removed_components(&mut self) {
+        self.clear_removed_components(self.removed_update_tick);
+        self.removed_update_tick = self.change_tick;
+    }
+
+    /// Forgets the removals recorded before a tick.
+    /// The schedule does this once every system has seen them.
+    pub fn clear_removed_components(&mut self, before: u32) {
+        let this_run = self.change_tick;
+        self.removed.values_mut().for_each(|removed| {
+            removed.retain(|(_, tick)| crate::genvec::is_newer(*tick, before, this_run))
+        });
+    }
+
+    fn record_removal(&mut self, type_id: TypeId, entity: Entity) {
+        let tick = self.change_tick;
+        self.removed
+            .entry(type_id)
+            .or_default()
+            .push((entity, tick));
+    }
+
+    /// Runs one of a component type's hooks, if it has been set
+    fn run_hook(
+        &mut self,
+        type_id: TypeId,
+        entity: Entity,
+        hook: impl FnOnce(&ComponentHooks) -> Option<&Hook>,
+    ) {
+        if let Some(hook) = self.hooks.get(&type_id).and_then(hook).cloned() {
+            hook(self, entity);
+        }
+    }
+
+    /// Chooses how components of type `T` are stored.
+    /// Components already in the world are moved into the new storage,
+    /// which must be empty. If the new storage rejects a component,
+    /// the components moved so far are put back and the previous storage is kept,
+    /// though the rejected component itself is dropped.
+    pub fn register_storage<T: 'static>(
+        &mut self,
+        storage: impl Storage<T> + 'static,
+    ) -> Result<()> {
+        if !storage.is_empty() {
+            return Err(Box::new(NonEmptyStorageError));
+        }
+        let mut storage: Box<dyn Storage<T>> = Box::new(storage);
+        let Some(mut previous) = self.components.remove(&TypeId::of::<T>()) else {
+            self.components.insert(TypeId::of::<T>(), Box::new(storage));
+            return Ok(());
+        };
+
+        let typed = previous
+            .as_any_mut()
+            .downcast_mut::<Box<dyn Storage<T>>>()
+            .expect("Component storage type mismatch!");
+        let handles = typed.handles().collect::<Vec<_>>();
+        if let Err((error, moved)) = move_components(typed.as_mut(), storage.as_mut(), &handles) {
+            if let Err((error, _)) =
+                move_components(storage.as_mut(), typed.as_mut(), &handles[..moved])
+            {
+                log::error!("Failed to restore components after a rejected storage: {error}");
+            }
+            self.components.insert(TypeId::of::<T>(), previous);
+            return Err(error);
+        }
+        self.components.insert(TypeId::of::<T>(), Box::new(storage));
+        Ok(())
+    }
+
+    /// Stores a singleton value, replacing any previous resource of the same type
+    pub fn insert_resource<T: 'static>(&mut self, resource: T) {
+        self.resources.insert(TypeId::of::<T>(), Box::new(resource));
+    }
+
+    pub fn remove_resource<T: 'static>(&mut self) -> Option<T> {
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .and_then(|resource| resource.downcast().ok())
+            .map(|resource| *resource)
+    }
+
+    pub fn resource<T: 'static>(&self) -> Option<&T> {
+        self.resources.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.resources.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    pub fn has_resource<T: 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Iterates over every entity that has all of the queried components
+    pub fn query<Q: Query>(&mut self) -> QueryIter<'_, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    /// Iterates over every entity that has all of the queried components
+    /// and passes the filter
+    pub fn query_filtered<Q: Query, F: QueryFilter>(&mut self) -> QueryIter<'_, Q, F> {
+        let mut access = Vec::new();
+        Q::access(&mut access);
+        assert!(
+            !is_aliased(&access),
+            "Query {} accesses a component mutably more than once!",
+            std::any::type_name::<Q>()
+        );
+
+        let state = Q::state(self);
+        let filter = F::state(self);
+        QueryIter::new(self, state, filter)
+    }
+
+    /// Iterates over every entity that has all of the queried components
+    /// without exclusive access to the world
+    pub fn query_ref<Q: ReadOnlyQuery>(&self) -> QueryIter<'_, Q> {
+        self.query_filtered_ref::<Q, ()>()
+    }
+
+    /// Iterates over every entity that has all of the queried components
+    /// and passes the filter, without exclusive access to the world
+    pub fn query_filtered_ref<Q: ReadOnlyQuery, F: QueryFilter>(&self) -> QueryIter<'_, Q, F> {
+        QueryIter::new(self, Q::state_ref(self), F::state(self))
+    }
+
+    fn storage_ptr<T: 'static>(&mut self) -> Option<*mut dyn Storage<T>> {
+        self.components
+            .get_mut(&TypeId::of::<T>())?
+            .as_any_mut()
+            .downcast_mut::<Box<dyn Storage<T>>>()
+            // Taken without a reference to the storage, so pointers from earlier calls stay valid
+            .map(|storage| std::ptr::addr_of_mut!(**storage))
+    }
+
+    /// Like `storage_ptr`, for storages that are only read through
+    fn storage_const_ptr<T: 'static>(&self) -> Option<*const dyn Storage<T>> {
+        self.components
+            .get(&TypeId::of::<T>())?
+            .as_any()
+            .downcast_ref::<Box<dyn Storage<T>>>()
+            .map(|storage| std::ptr::addr_of!(**storage))
+    }
+
+    fn storage<T: 'static>(&self) -> Option<&dyn Storage<T>> {
+        self.components
+            .get(&TypeId::of::<T>())?
+            .as_any()
+            .downcast_ref::<Box<dyn Storage<T>>>()
+            .map(|storage| storage.as_ref())
+    }
+
+    /// The storage for `T`, defaulting to a `GenerationalVec`
+    /// unless another storage was registered
+    fn storage_mut<T: 'static>(&mut self) -> &mut dyn Storage<T> {
+        self.components
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| {
+                let storage: Box<dyn Storage<T>> = Box::new(GenerationalVec::<T>::new(Vec::new()));
+                Box::new(storage)
+            })
+            .as_any_mut()
+            .downcast_mut::<Box<dyn Storage<T>>>()
+            .expect("Component storage type mismatch!")
+            .as_mut()
+    }
+}
+
+/// Whether a type is accessed more than once with at least one of the accesses writing to it
+pub(crate) fn is_aliased(access: &[(TypeId, bool)]) -> bool {
+    access
+        .iter()
+        .enumerate()
+        .any(|(index, (type_id, mutable))| {
+            access
+                .iter()
+                .skip(index + 1)
+                .any(|(other_type_id, other_mutable)| {
+                    other_type_id == type_id && (*mutable || *other_mutable)
+                })
+        })
+}
+
+/// Moves components along with their ticks, stopping at the first one
+/// the destination rejects. The error comes with the number of handles moved before it.
+fn move_components<T>(
+    from: &mut dyn Storage<T>,
+    to: &mut dyn Storage<T>,
+    handles: &[Entity],
+) -> std::result::Result<(), (Box<dyn std::error::Error>, usize)> {
+    for (moved, handle) in handles.iter().enumerate() {
+        let ticks = from.ticks(*handle);
+        if let Some(component) = from.take(*handle) {
+            to.insert(*handle, component)
+                .map_err(|error| (error, moved))?;
+            if let Some(ticks) = ticks {
+                to.set_ticks(*handle, ticks);
+            }
+        }
+    }
+    Ok(())
+}
+
+type Hook = Rc<dyn Fn(&mut World, Entity)>;
+
+/// Callbacks for the lifecycle of one component type.
+///
+/// `on_add` runs after a component is added to an entity that did not have one,
+/// `on_replace` runs before an existing component is overwritten
+/// and `on_remove` runs before a component is removed or its entity despawned,
+/// so the old component can still be read from the world.
+#[derive(Default, Clone)]
+pub struct ComponentHooks {
+    on_add: Option<Hook>,
+    on_replace: Option<Hook>,
+    on_remove: Option<Hook>,
+}
+
+impl ComponentHooks {
+    pub fn on_add(&mut self, hook: impl Fn(&mut World, Entity) + 'static) -> &mut Self {
+        self.on_add = Some(Rc::new(hook));
+        self
+    }
+
+    pub fn on_replace(&mut self, hook: impl Fn(&mut World, Entity) + 'static) -> &mut Self {
+        self.on_replace = Some(Rc::new(hook));
+        self
+    }
+
+    pub fn on_remove(&mut self, hook: impl Fn(&mut World, Entity) + 'static) -> &mut Self {
+        self.on_remove = Some(Rc::new(hook));
+        self
+    }
+}
+
+/// The entities that lost a component of type `T`, oldest first
+pub struct RemovedComponents<'w, T> {
+    removed: std::slice::Iter<'w, (Entity, u32)>,
+    last_run: u32,
+    this_run: u32,
+    _component: PhantomData<fn() -> T>,
+}
+
+impl<T> Iterator for RemovedComponents<'_, T> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.removed
+            .by_ref()
+            .find(|(_, tick)| crate::genvec::is_newer(*tick, self.last_run, self.this_run))
+            .map(|(entity, _)| *entity)
+    }
+}
+
+/// Type-erased access to a component storage so the world
+/// can hold one storage per component type
+trait ComponentStorage {
+    fn contains(&self, entity: Entity) -> bool;
+    fn remove(&mut self, entity: Entity);
+    fn clamp_ticks(&mut self, this_run: u32);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> ComponentStorage for Box<dyn Storage<T>> {
+    fn contains(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        Storage::remove(self.as_mut(), entity);
+    }
+
+    fn clamp_ticks(&mut self, this_run: u32) {
+        Storage::clamp_ticks(self.as_mut(), this_run);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Describes the data a query fetches for each entity.
+///
+/// Implemented for `Entity`, `&T`, `&mut T`, `Option<&T>`, `Option<&mut T>`
+/// and tuples of those. Components queried with `&mut T` are fetched as `Mut<T>`.
+pub trait Query {
+    type Item<'w>;
+    type State;
+
+    /// Records the component types this query reads and whether they are written
+    fn access(access: &mut Vec<(TypeId, bool)>);
+
+    fn state(world: &mut World) -> Self::State;
+
+    /// # Safety
+    ///
+    /// The storages referenced by the state must outlive `'w`,
+    /// and no other live reference may alias the fetched components.
+    unsafe fn fetch<'w>(state: &Self::State, entity: Entity) -> Option<Self::Item<'w>>;
+
+    /// The only entities this query can match, if it requires a component
+    ///
+    /// # Safety
+    ///
+    /// The storages referenced by the state must still be alive.
+    unsafe fn candidates(_state: &Self::State) -> Option<Vec<Entity>> {
+        None
+    }
+}
+
+/// A query that never writes to the components it fetches,
+/// so it can run without exclusive access to the world
+///
+/// # Safety
+///
+/// `fetch` must only read through the state, and `state_ref`
+/// must build the same state `state` would.
+pub unsafe trait ReadOnlyQuery: Query {
+    fn state_ref(world: &World) -> Self::State;
+}
+
+impl Query for Entity {
+    type Item<'w> = Entity;
+    type State = ();
+
+    fn access(_access: &mut Vec<(TypeId, bool)>) {}
+
+    fn state(_world: &mut World) -> Self::State {}
+
+    unsafe fn fetch<'w>(_state: &Self::State, entity: Entity) -> Option<Self::Item<'w>> {
+        Some(entity)
+    }
+}
+
+unsafe impl ReadOnlyQuery for Entity {
+    fn state_ref(_world: &World) -> Self::State {}
+}
+
+impl<T: 'static> Query for &T {
+    type Item<'w> = &'w T;
+    type State = Option<*const dyn Storage<T>>;
+
+    fn access(access: &mut Vec<(TypeId, bool)>) {
+        access.push((TypeId::of::<T>(), false));
+    }
+
+    fn state(world: &mut World) -> Self::State {
+        world.storage_const_ptr::<T>()
+    }
+
+    unsafe fn fetch<'w>(state: &Self::State, entity: Entity) -> Option<Self::Item<'w>> {
+        let (value, _) = (*(*state)?).get_ptr(entity)?;
+        Some(&*value)
+    }
+
+    unsafe fn candidates(state: &Self::State) -> Option<Vec<Entity>> {
+        Some(storage_handles(*state))
+    }
+}
+
+unsafe impl<T: 'static> ReadOnlyQuery for &T {
+    fn state_ref(world: &World) -> Self::State {
+        world.storage_const_ptr::<T>()
+    }
+}
+
+impl<T: 'static> Query for &mut T {
+    type Item<'w> = Mut<'w, T>;
+    type State = (Option<*mut dyn Storage<T>>, u32);
+
+    fn access(access: &mut Vec<(TypeId, bool)>) {
+        access.push((TypeId::of::<T>(), true));
+    }
+
+    fn state(world: &mut World) -> Self::State {
+        (world.storage_ptr::<T>(), world.change_tick)
+    }
+
+    unsafe fn fetch<'w>(state: &Self::State, entity: Entity) -> Option<Self::Item<'w>> {
+        let (storage, change_tick) = *state;
+        let (value, ticks) = (*storage?).get_mut_ptr(entity)?;
+        Some(Mut {
+            value: &mut *value,
+            ticks: ticks.map(|ticks| &mut *ticks),
+            change_tick,
+        })
+    }
+
+    unsafe fn candidates(state: &Self::State) -> Option<Vec<Entity>> {
+        Some(storage_handles(
+            state.0.map(|storage| storage as *const dyn Storage<T>),
+        ))
+    }
+}
+
+/// A mutably queried component that is marked as changed when it is written to
+pub struct Mut<'w, T> {
+    value: &'w mut T,
+    ticks: Option<&'w mut ComponentTicks>,
+    change_tick: u32,
+}
+
+impl<'w, T> Mut<'w, T> {
+    /// Marks the component as changed and releases the plain reference
+    pub fn into_inner(mut self) -> &'w mut T {
+        self.set_changed();
+        self.value
+    }
+
+    /// Writes to the component without marking it as changed
+    pub fn bypass_change_detection(&mut self) -> &mut T {
+        self.value
+    }
+
+    pub fn set_changed(&mut self) {
+        if let Some(ticks) = self.ticks.as_mut() {
+            ticks.changed = self.change_tick;
+        }
+    }
+}
+
+impl<T> std::ops::Deref for Mut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<T> std::ops::DerefMut for Mut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.set_changed();
+        self.value
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Mut<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+/// # Safety
+///
+/// The storage must still be alive.
+unsafe fn storage_handles<T>(storage: Option<*const dyn Storage<T>>) -> Vec<Entity> {
+    match storage {
+        Some(storage) => (*storage).handles().collect(),
+        None => Vec::new(),
+    }
+}
+
+impl<Q: Query> Query for Option<Q> {
+    type Item<'w> = Option<Q::Item<'w>>;
+    type State = Q::State;
+
+    fn access(access: &mut Vec<(TypeId, bool)>) {
+        Q::access(access);
+    }
+
+    fn state(world: &mut World) -> Self::State {
+        Q::state(world)
+    }
+
+    unsafe fn fetch<'w>(state: &Self::State, entity: Entity) -> Option<Self::Item<'w>> {
+        Some(Q::fetch(state, entity))
+    }
+}
+
+unsafe impl<Q: ReadOnlyQuery> ReadOnlyQuery for Option<Q> {
+    fn state_ref(world: &World) -> Self::State {
+        Q::state_ref(world)
+    }
+}
+
+/// Narrows down the entities a query visits without fetching any data
+pub trait QueryFilter {
+    type State;
+
+    fn state(world: &World) -> Self::State;
+
+    /// # Safety
+    ///
+    /// The storages referenced by the state must still be alive.
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool;
+}
+
+/// Only matches entities that have a `T` component
+pub struct With<T>(PhantomData<T>);
+
+impl<T: 'static> QueryFilter for With<T> {
+    type State = Option<*const dyn Storage<T>>;
+
+    fn state(world: &World) -> Self::State {
+        world.storage_const_ptr::<T>()
+    }
+
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+        state.is_some_and(|storage| (*storage).get_ptr(entity).is_some())
+    }
+}
+
+/// Only matches entities that do not have a `T` component
+pub struct Without<T>(PhantomData<T>);
+
+impl<T: 'static> QueryFilter for Without<T> {
+    type State = Option<*const dyn Storage<T>>;
+
+    fn state(world: &World) -> Self::State {
+        world.storage_const_ptr::<T>()
+    }
+
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+        !With::<T>::matches(state, entity)
+    }
+}
+
+/// Only matches entities whose `T` component was added since the last run.
+/// Storages that do not record change ticks always match.
+pub struct Added<T>(PhantomData<T>);
+
+impl<T: 'static> QueryFilter for Added<T> {
+    type State = (Option<*const dyn Storage<T>>, u32, u32);
+
+    fn state(world: &World) -> Self::State {
+        (
+            world.storage_const_ptr::<T>(),
+            world.last_run_tick,
+            world.change_tick,
+        )
+    }
+
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+        let (storage, last_run, this_run) = *state;
+        With::<T>::matches(&storage, entity)
+            && storage.is_some_and(|storage| match (*storage).get_ptr(entity) {
+                Some((_, Some(ticks))) => (*ticks).is_added(last_run, this_run),
+                _ => true,
+            })
+    }
+}
+
+/// Only matches entities whose `T` component was added or changed since the last run.
+/// Storages that do not record change ticks always match.
+pub struct Changed<T>(PhantomData<T>);
+
+impl<T: 'static> QueryFilter for Changed<T> {
+    type State = (Option<*const dyn Storage<T>>, u32, u32);
+
+    fn state(world: &World) -> Self::State {
+        Added::<T>::state(world)
+    }
+
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+        let (storage, last_run, this_run) = *state;
+        With::<T>::matches(&storage, entity)
+            && storage.is_some_and(|storage| match (*storage).get_ptr(entity) {
+                Some((_, Some(ticks))) => (*ticks).is_changed(last_run, this_run),
+                _ => true,
+            })
+    }
+}
+
+impl QueryFilter for () {
+    type State = ();
+
+    fn state(_world: &World) -> Self::State {}
+
+    unsafe fn matches(_state: &Self::State, _entity: Entity) -> bool {
+        true
+    }
+}
+
+macro_rules! impl_query_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: Query),+> Query for ($($name,)+) {
+            type Item<'w> = ($($name::Item<'w>,)+);
+            type State = ($($name::State,)+);
+
+            fn access(access: &mut Vec<(TypeId, bool)>) {
+                $($name::access(access);)+
+            }
+
+            fn state(world: &mut World) -> Self::State {
+                ($($name::state(world),)+)
+            }
+
+            #[allow(non_snake_case)]
+            unsafe fn fetch<'w>(state: &Self::State, entity: Entity) -> Option<Self::Item<'w>> {
+                let ($($name,)+) = state;
+                Some(($($name::fetch($name, entity)?,)+))
+            }
+
+            #[allow(non_snake_case)]
+            unsafe fn candidates(state: &Self::State) -> Option<Vec<Entity>> {
+                let ($($name,)+) = state;
+                [$($name::candidates($name)),+]
+                    .into_iter()
+                    .flatten()
+                    .min_by_key(Vec::len)
+            }
+        }
+
+        unsafe impl<$($name: ReadOnlyQuery),+> ReadOnlyQuery for ($($name,)+) {
+            fn state_ref(world: &World) -> Self::State {
+                ($($name::state_ref(world),)+)
+            }
+        }
+
+        impl<$($name: QueryFilter),+> QueryFilter for ($($name,)+) {
+            type State = ($($name::State,)+);
+
+            fn state(world: &World) -> Self::State {
+                ($($name::state(world),)+)
+            }
+
+            #[allow(non_snake_case)]
+            unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+                let ($($name,)+) = state;
+                $($name::matches($name, entity))&&+
+            }
+        }
+    };
+}
+
+impl_query_tuple!(A);
+impl_query_tuple!(A, B);
+impl_query_tuple!(A, B, C);
+impl_query_tuple!(A, B, C, D);
+impl_query_tuple!(A, B, C, D, E);
+impl_query_tuple!(A, B, C, D, E, F);
+impl_query_tuple!(A, B, C, D, E, F, G);
+impl_query_tuple!(A, B, C, D, E, F, G, H);
+
+pub struct QueryIter<'w, Q: Query, F: QueryFilter = ()> {
+    entities: std::vec::IntoIter<Entity>,
+    state: Q::State,
+    filter: F::State,
+    _world: PhantomData<&'w mut World>,
+}
+
+impl<'w, Q: Query, F: QueryFilter> QueryIter<'w, Q, F> {
+    fn new(world: &World, state: Q::State, filter: F::State) -> Self {
+        // Only visit the entities of the smallest required storage when there is one
+        let entities = unsafe { Q::candidates(&state) }.unwrap_or_else(|| world.entities());
+        Self {
+            entities: entities.into_iter(),
+            state,
+            filter,
+            _world: PhantomData,
+        }
+    }
+}
+
+impl<'w, Q: Query, F: QueryFilter> Iterator for QueryIter<'w, Q, F> {
+    type Item = Q::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for entity in self.entities.by_ref() {
+            // Safety: the iterator borrows the world for 'w, mutably unless
+            // the query is read only, aliasing was ruled out when the query was created,
+            // and every entity is visited at most once. Filters and fetches
+            // only borrow the current entity's components through `get_ptr`
+            // and `get_mut_ptr`, so items returned earlier stay valid.
+            unsafe {
+                if !F::matches(&self.filter, entity) {
+                    continue;
+                }
+                if let Some(item) = Q::fetch(&self.state, entity) {
+                    return Some(item);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug)]
+pub struct NonEmptyStorageError;
+
+impl std::error::Error for NonEmptyStorageError {}
+
+impl std::fmt::Display for NonEmptyStorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Storages must be empty when they are registered.")
+    }
+}
+
+#[cfg(feature = "serde")]
+pub use self::prefab::*;
+
+#[cfg(feature = "serde")]
+mod prefab {
+    use super::{Entity, World};
+    use crate::{
+        genvec::Result,
+        scene::{EntityMap, SceneEntity, SceneRegistry},
+    };
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashMap};
+
+    /// Components keyed by registered name, each paired with the prefab level
+    /// whose entity ids it references
+    type PendingComponents<'a> = BTreeMap<&'a str, (&'a serde_json::Value, usize)>;
+
+    #[derive(Debug)]
+    pub struct EmptyPrefabError;
+
+    impl std::error::Error for EmptyPrefabError {}
+
+    impl std::fmt::Display for EmptyPrefabError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "Prefab has no root entity.")
+        }
+    }
+
+    /// A reusable entity template that can be instantiated into a world many times.
+    ///
+    /// The first entity is the root of every instance. Entity ids are local to the prefab,
+    /// and references to them are remapped to the spawned entities of each instance.
+    #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct Prefab {
+        pub entities: Vec<PrefabEntity>,
+    }
+
+    #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct PrefabEntity {
+        /// The id other entities in the prefab use to reference this one
+        pub entity: Entity,
+        /// A prefab this entity is an instance of,
+        /// with the components below overriding those of its root
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub prefab: Option<Box<Prefab>>,
+        #[serde(default)]
+        pub components: BTreeMap<String, serde_json::Value>,
+    }
+
+    /// Components that replace those of a prefab for a single instance
+    #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct PrefabOverrides {
+        pub entities: Vec<SceneEntity>,
+    }
+
+    impl PrefabOverrides {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Replaces a component of the prefab entity with the given local id
+        pub fn set(
+            &mut self,
+            entity: Entity,
+            name: impl Into<String>,
+            component: impl Serialize,
+        ) -> Result<&mut Self> {
+            let value = serde_json::to_value(component)?;
+            match self
+                .entities
+                .iter_mut()
+                .find(|overridden| overridden.entity == entity)
+            {
+                Some(overridden) => {
+                    overridden.components.insert(name.into(), value);
+                }
+                None => self.entities.push(SceneEntity {
+                    entity,
+                    components: BTreeMap::from([(name.into(), value)]),
+                }),
+            }
+            Ok(self)
+        }
+    }
+
+    /// The entities spawned for one instance of a prefab
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct PrefabInstance {
+        pub root: Entity,
+        /// Maps the prefab's own entity ids to the spawned entities,
+        /// which does not include the entities of nested prefabs
+        pub entity_map: EntityMap,
+    }
+
+    impl Prefab {
+        /// Captures an entity and all of its descendants,
+        /// using their current handles as the prefab's entity ids.
+        /// The root's parent is not captured, so instances are spawned unparented.
+        pub fn from_entity(world: &World, registry: &SceneRegistry, root: Entity) -> Result<Self> {
+            let mut entities = Vec::new();
+            let mut stack = vec![root];
+            while let Some(entity) = stack.pop() {
+                if let Some(children) = world.get_component::<crate::hierarchy::Children>(entity) {
+                    stack.extend(children.0.iter().rev());
+                }
+                let mut components = registry.save(world, entity)?;
+                if entity == root {
+                    if let Some(name) = registry.name_of::<crate::hierarchy::Parent>() {
+                        components.remove(name);
+                    }
+                }
+                entities.push(PrefabEntity {
+                    entity,
+                    prefab: None,
+                    components,
+                });
+            }
+            Ok(Self { entities })
+        }
+
+        /// The local id of the root entity
+        pub fn root(&self) -> Option<Entity> {
+            self.entities
+                .first()
+                .map(|prefab_entity| prefab_entity.entity)
+        }
+
+        pub fn instantiate(
+            &self,
+            world: &mut World,
+            registry: &SceneRegistry,
+        ) -> Result<PrefabInstance> {
+            self.instantiate_with(world, registry, &PrefabOverrides::default())
+        }
+
+        /// Spawns a new instance of the prefab, replacing any overridden components
+        pub fn instantiate_with(
+            &self,
+            world: &mut World,
+            registry: &SceneRegistry,
+            overrides: &PrefabOverrides,
+        ) -> Result<PrefabInstance> {
+            let overrides = overrides
+                .entities
+                .iter()
+                .map(|overridden| (overridden.entity, pending(&overridden.components, 0)))
+                .collect();
+
+            // Spawn every entity first so components can reference any of them
+            let mut entity_maps = Vec::new();
+            let mut spawned = Vec::new();
+            let root = self
+                .spawn(world, &overrides, &mut entity_maps, &mut spawned)
+                .and_then(|root| {
+                    for (entity, components) in spawned.iter() {
+                        for (name, (value, level)) in components {
+                            registry.load(
+                                world,
+                                *entity,
+                                name,
+                                (*value).clone(),
+                                &entity_maps[*level],
+                            )?;
+                        }
+                    }
+                    Ok(root)
+                });
+
+            // A failed instance leaves nothing behind
+            let root = match root {
+                Ok(root) => root,
+                Err(error) => {
+                    for (entity, _) in spawned {
+                        world.despawn(entity);
+                    }
+                    return Err(error);
+                }
+            };
+
+            Ok(PrefabInstance {
+                root,
+                entity_map: entity_maps.swap_remove(0),
+            })
+        }
+
+        /// Spawns the entities of this prefab and its nested prefabs,
+        /// recording one entity map per prefab so references stay local to each
+        fn spawn<'a>(
+            &'a self,
+            world: &mut World,
+            overrides: &HashMap<Entity, PendingComponents<'a>>,
+            entity_maps: &mut Vec<EntityMap>,
+            spawned: &mut Vec<(Entity, PendingComponents<'a>)>,
+        ) -> Result<Entity> {
+            let root = self.root().ok_or(EmptyPrefabError)?;
+            let level = entity_maps.len();
+            entity_maps.push(EntityMap::default());
+
+            for prefab_entity in self.entities.iter() {
+                let mut components = pending(&prefab_entity.components, level);
+                if let Some(overridden) = overrides.get(&prefab_entity.entity) {
+                    components.extend(overridden.iter().map(|(name, value)| (*name, *value)));
+                }
+
+                let entity = match &prefab_entity.prefab {
+                    Some(nested) => {
+                        let nested_root = nested.root().ok_or(EmptyPrefabError)?;
+                        let nested_overrides = HashMap::from([(nested_root, components)]);
+                        nested.spawn(world, &nested_overrides, entity_maps, spawned)?
+                    }
+                    None => {
+                        let entity = world.spawn();
+                        spawned.push((entity, components));
+                        entity
+                    }
+                };
+                entity_maps[level].0.insert(prefab_entity.entity, entity);
+            }
+
+            Ok(entity_maps[level].map(root))
+        }
+
+        pub fn to_ron(&self) -> Result<String> {
+            Ok(ron::ser::to_string_pretty(
+                self,
+                ron::ser::PrettyConfig::default(),
+            )?)
+        }
+
+        pub fn from_ron(source: &str) -> Result<Self> {
+            Ok(ron::from_str(source)?)
+        }
+
+        pub fn to_json(&self) -> Result<String> {
+            Ok(serde_json::to_string_pretty(self)?)
+        }
+
+        pub fn from_json(source: &str) -> Result<Self> {
+            Ok(serde_json::from_str(source)?)
+        }
+    }
+
+    fn pending(
+        components: &BTreeMap<String, serde_json::Value>,
+        level: usize,
+    ) -> PendingComponents<'_> {
+        components
+            .iter()
+            .map(|(name, value)| (name.as_str(), (value, level)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(f32, f32);
+
+    #[derive(Debug, PartialEq)]
+    struct Velocity(f32, f32);
+
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+
+    struct Frozen;
+
+    #[test]
+    fn spawn_and_despawn() {
+        let mut world = World::new();
+
+        let first = world.spawn();
+        let second = world.spawn();
+        assert!(world.is_alive(first));
+        assert_eq!(world.entities(), &[first, second]);
+
+        world.despawn(first);
+        assert!(!world.is_alive(first));
+        assert_eq!(world.entities(), &[second]);
+
+        // The freed slot is reused with a new generation
+        let third = world.spawn();
+        assert_eq!(third.index(), first.index());
+        assert!(!world.is_alive(first));
+        assert!(world.is_alive(third));
+    }
+
+    #[test]
+    fn reserved_entities() -> Result<()> {
+        let mut world = World::new();
+        let spawned = world.spawn();
+        let reserver = world.entity_reserver();
+
+        let reserved = std::thread::scope(|scope| {
+            let workers = (0..4)
+                .map(|_| scope.spawn(|| (0..50).map(|_| reserver.reserve()).collect::<Vec<_>>()))
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        let entity = world.reserve_entity();
+        assert!(!world.is_alive(entity));
+        assert!(world.add_component(entity, 1_u32).is_err());
+
+        world.flush_reserved_entities();
+        assert!(world.is_alive(entity));
+        assert!(reserved.iter().all(|entity| world.is_alive(*entity)));
+        assert_eq!(world.entities().len(), reserved.len() + 2);
+        world.add_component(entity, 1_u32)?;
+        assert!(world.is_alive(spawned));
+
+        Ok(())
+    }
+
+    #[test]
+    fn spawning_leaves_reservations_pending() {
+        let mut world = World::new();
+        let reserved = world.reserve_entity();
+        let spawned = world.spawn();
+        assert_ne!(reserved, spawned);
+        assert!(world.is_alive(spawned));
+        assert!(!world.is_alive(reserved));
+        assert_eq!(world.entities(), &[spawned]);
+
+        world.despawn(spawned);
+        world.flush_reserved_entities();
+        assert!(world.is_alive(reserved));
+        assert!(!world.is_alive(spawned));
+        assert_eq!(world.entities(), &[reserved]);
+    }
+
+    #[test]
+    fn components() -> Result<()> {
+        let mut world = World::new();
+        let entity = world.spawn();
+
+        world.add_component(entity, Position(1.0, 2.0))?;
+        world.add_component(entity, Name("player"))?;
+        assert_eq!(world.get_component(entity), Some(&Position(1.0, 2.0)));
+        assert_eq!(world.get_component(entity), Some(&Name("player")));
+
+        if let Some(position) = world.get_component_mut::<Position>(entity) {
+            position.0 = 5.0;
+        }
+        assert_eq!(world.get_component(entity), Some(&Position(5.0, 2.0)));
+
+        world.remove_component::<Position>(entity);
+        assert!(!world.has_component::<Position>(entity));
+        assert!(world.has_component::<Name>(entity));
+
+        Ok(())
+    }
+
+    #[test]
+    fn despawned_entities_lose_their_components() -> Result<()> {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.add_component(entity, Name("enemy"))?;
+
+        world.despawn(entity);
+        assert!(world.get_component::<Name>(entity).is_none());
+        assert!(world.add_component(entity, Name("enemy")).is_err());
+
+        // A new entity in the same slot does not inherit the old components
+        let replacement = world.spawn();
+        assert!(world.get_component::<Name>(replacement).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_mutable_and_immutable() -> Result<()> {
+        let mut world = World::new();
+        let moving = world.spawn();
+        world.add_component(moving, Position(0.0, 0.0))?;
+        world.add_component(moving, Velocity(1.0, 2.0))?;
+        let stationary = world.spawn();
+        world.add_component(stationary, Position(5.0, 5.0))?;
+
+        for (mut position, velocity) in world.query::<(&mut Position, &Velocity)>() {
+            position.0 += velocity.0;
+            position.1 += velocity.1;
+        }
+
+        assert_eq!(world.get_component(moving), Some(&Position(1.0, 2.0)));
+        assert_eq!(world.get_component(stationary), Some(&Position(5.0, 5.0)));
+        assert_eq!(world.query::<&Position>().count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_optional_components() -> Result<()> {
+        let mut world = World::new();
+        let named = world.spawn();
+        world.add_component(named, Position(0.0, 0.0))?;
+        world.add_component(named, Name("named"))?;
+        let anonymous = world.spawn();
+        world.add_component(anonymous, Position(1.0, 1.0))?;
+
+        let results = world
+            .query::<(Entity, &Position, Option<&Name>)>()
+            .map(|(entity, _, name)| (entity, name.map(|name| name.0)))
+            .collect::<Vec<_>>();
+        assert_eq!(results, [(named, Some("named")), (anonymous, None)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_filters() -> Result<()> {
+        let mut world = World::new();
+        let frozen = world.spawn();
+        world.add_component(frozen, Position(0.0, 0.0))?;
+        world.add_component(frozen, Frozen)?;
+        let free = world.spawn();
+        world.add_component(free, Position(0.0, 0.0))?;
+
+        let with = world
+            .query_filtered::<Entity, With<Frozen>>()
+            .collect::<Vec<_>>();
+        assert_eq!(with, [frozen]);
+
+        let without = world
+            .query_filtered::<Entity, (With<Position>, Without<Frozen>)>()
+            .collect::<Vec<_>>();
+        assert_eq!(without, [free]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_only_queries_share_the_world() -> Result<()> {
+        let mut world = World::new();
+        let named = world.spawn();
+        world.add_component(named, Position(0.0, 0.0))?;
+        world.add_component(named, Name("named"))?;
+        let frozen = world.spawn();
+        world.add_component(frozen, Position(1.0, 1.0))?;
+        world.add_component(frozen, Frozen)?;
+
+        let world = &world;
+        let names = world
+            .query_ref::<(Entity, Option<&Name>)>()
+            .map(|(entity, name)| (entity, name.map(|name| name.0)))
+            .collect::<Vec<_>>();
+        assert_eq!(names, [(named, Some("named")), (frozen, None)]);
+
+        // Several read only queries can be held at once
+        let positions = world.query_ref::<&Position>();
+        let free = world.query_filtered_ref::<(Entity, &Position), Without<Frozen>>();
+        assert_eq!(positions.count(), 2);
+        assert_eq!(free.map(|(entity, _)| entity).collect::<Vec<_>>(), [named]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_skips_stale_handles() -> Result<()> {
+        let mut world = World::new();
+        let despawned = world.spawn();
+        world.add_component(despawned, Position(0.0, 0.0))?;
+        world.despawn(despawned);
+
+        let replacement = world.spawn();
+        world.add_component(replacement, Position(1.0, 1.0))?;
+
+        let entities = world.query::<(Entity, &Position)>().collect::<Vec<_>>();
+        assert_eq!(entities, [(replacement, &Position(1.0, 1.0))]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_items_can_be_held_together() -> Result<()> {
+        let mut world = World::new();
+        world.register_storage::<Name>(crate::genvec::SparseSet::new())?;
+        for index in 0..3 {
+            let entity = world.spawn();
+            world.add_component(entity, Position(index as f32, 0.0))?;
+            world.add_component(entity, Name("entity"))?;
+        }
+
+        let mut positions = world.query::<&mut Position>().collect::<Vec<_>>();
+        for position in &mut positions {
+            position.1 += 1.0;
+        }
+        let mut names = world
+            .query_filtered::<&mut Name, With<Name>>()
+            .collect::<Vec<_>>();
+        for name in &mut names {
+            name.0 = "renamed";
+        }
+
+        assert!(world.query::<&Position>().all(|position| position.1 == 1.0));
+        assert!(world.query::<&Name>().all(|name| name.0 == "renamed"));
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn query_rejects_aliased_mutable_access() {
+        let mut world = World::new();
+        world.query::<(&mut Position, &Position)>().for_each(drop);
+    }
+
+    #[test]
+    fn resources() {
+        #[derive(Debug, PartialEq)]
+        struct Score(u32);
+
+        let mut world = World::new();
+        assert!(world.resource::<Score>().is_none());
+
+        world.insert_resource(Score(0));
+        if let Some(score) = world.resource_mut::<Score>() {
+            score.0 += 10;
+        }
+        assert_eq!(world.resource(), Some(&Score(10)));
+
+        world.insert_resource(Score(3));
+        assert_eq!(world.resource(), Some(&Score(3)));
+
+        assert_eq!(world.remove_resource(), Some(Score(3)));
+        assert!(!world.has_resource::<Score>());
+    }
+
+    #[test]
+    fn rejected_storages_keep_the_previous_one() -> Result<()> {
+        use crate::genvec::{error::GenerationError, SparseSet};
+
+        /// A sparse set that refuses one handle
+        struct Picky(SparseSet<Name>, Entity);
+
+        unsafe impl Storage<Name> for Picky {
+            fn insert(&mut self, handle: Handle, value: Name) -> Result<()> {
+                if handle == self.1 {
+                    return Err(Box::new(GenerationError { handle }));
+                }
+                self.0.insert(handle, value)
+            }
+
+            fn remove(&mut self, handle: Handle) {
+                self.0.remove(handle)
+            }
+
+            fn take(&mut self, handle: Handle) -> Option<Name> {
+                self.0.take(handle)
+            }
+
+            fn get(&self, handle: Handle) -> Option<&Name> {
+                self.0.get(handle)
+            }
+
+            fn get_mut(&mut self, handle: Handle) -> Option<&mut Name> {
+                self.0.get_mut(handle)
+            }
+
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+
+            fn is_empty(&self) -> bool {
+                self.0.is_empty()
+            }
+
+            fn clear(&mut self) {
+                self.0.clear()
+            }
+
+            fn handles(&self) -> Box<dyn Iterator<Item = Handle> + '_> {
+                Box::new(self.0.handles())
+            }
+
+            fn get_ptr(
+                &self,
+                handle: Handle,
+            ) -> Option<(*const Name, Option<*const ComponentTicks>)> {
+                Storage::get_ptr(&self.0, handle)
+            }
+
+            fn get_mut_ptr(
+                &mut self,
+                handle: Handle,
+            ) -> Option<(*mut Name, Option<*mut ComponentTicks>)> {
+                Storage::get_mut_ptr(&mut self.0, handle)
+            }
+        }
+
+        let mut world = World::new();
+        let [first, second, third] = [(); 3].map(|_| world.spawn());
+        world.add_component(first, Name("first"))?;
+        world.add_component(second, Name("second"))?;
+        world.add_component(third, Name("third"))?;
+
+        assert!(world
+            .register_storage::<Name>(Picky(SparseSet::new(), third))
+            .is_err());
+
+        // Everything but the rejected component is back in the previous storage
+        assert_eq!(world.get_component(first), Some(&Name("first")));
+        assert_eq!(world.get_component(second), Some(&Name("second")));
+        assert!(world.get_component::<Name>(third).is_none());
+        assert_eq!(world.query::<&Name>().count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sparse_set_storage() -> Result<()> {
+        let mut world = World::new();
+        let first = world.spawn();
+        world.add_component(first, Name("first"))?;
+
+        // Existing components move over to the newly registered storage
+        world.register_storage::<Name>(crate::genvec::SparseSet::new())?;
+        assert_eq!(world.get_component(first), Some(&Name("first")));
+
+        // Storages that already hold values are rejected
+        let mut storage = GenerationalVec::new(Vec::new());
+        storage.insert(first, Name("replaced"))?;
+        assert!(world.register_storage::<Name>(storage).is_err());
+        assert_eq!(world.get_component(first), Some(&Name("first")));
+
+        let second = world.spawn();
+        world.add_component(second, Name("second"))?;
+        world.add_component(second, Position(0.0, 0.0))?;
+        world.remove_component::<Name>(first);
+
+        let names = world
+            .query::<(Entity, &Name, Option<&Position>)>()
+            .map(|(entity, name, position)| (entity, name.0, position.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(names, [(second, "second", true)]);
+
+        world.despawn(second);
+        assert_eq!(world.query::<&Name>().count(), 0);
+
+        Ok(())
+    }
+
+    /// Makes filters only see writes from here on, as if a system had just started
+    fn start_run(world: &mut World) {
+        let tick = world.increment_change_tick();
+        world.set_last_run_tick(tick);
+    }
+
+    #[test]
+    fn added_and_changed_filters() -> Result<()> {
+        let mut world = World::new();
+        let first = world.spawn();
+        let second = world.spawn();
+        world.add_component(first, Position(0.0, 0.0))?;
+        world.add_component(second, Position(1.0, 1.0))?;
+
+        // Everything is new to a query that has never run
+        assert_eq!(world.query_filtered::<Entity, Added<Position>>().count(), 2);
+
+        start_run(&mut world);
+        assert_eq!(world.query_filtered::<Entity, Added<Position>>().count(), 0);
+        assert_eq!(
+            world.query_filtered::<Entity, Changed<Position>>().count(),
+            0
+        );
+
+        // Only writes through a mutable query mark components as changed
+        for (entity, mut position) in world.query::<(Entity, &mut Position)>() {
+            if entity == second {
+                position.0 += 1.0;
+            }
+        }
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Changed<Position>>()
+                .collect::<Vec<_>>(),
+            [second]
+        );
+        assert_eq!(world.query_filtered::<Entity, Added<Position>>().count(), 0);
+
+        // Replacing a component changes it without adding it again
+        start_run(&mut world);
+        world.add_component(first, Position(2.0, 2.0))?;
+        let third = world.spawn();
+        world.add_component(third, Position(3.0, 3.0))?;
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Changed<Position>>()
+                .collect::<Vec<_>>(),
+            [first, third]
+        );
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Added<Position>>()
+                .collect::<Vec<_>>(),
+            [third]
+        );
+
+        start_run(&mut world);
+        world.get_component_mut::<Position>(first);
+        for mut position in world.query::<&mut Position>() {
+            position.bypass_change_detection().0 = 0.0;
+        }
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Changed<Position>>()
+                .collect::<Vec<_>>(),
+            [first]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ticks_survive_storage_registration() -> Result<()> {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.add_component(entity, Name("entity"))?;
+        start_run(&mut world);
+
+        world.register_storage::<Name>(crate::genvec::SparseSet::new())?;
+        assert_eq!(world.query_filtered::<Entity, Changed<Name>>().count(), 0);
+        assert_eq!(world.get_component(entity), Some(&Name("entity")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sparse_set_change_detection() -> Result<()> {
+        let mut world = World::new();
+        world.register_storage::<Name>(crate::genvec::SparseSet::new())?;
+        let first = world.spawn();
+        let second = world.spawn();
+        world.add_component(first, Name("first"))?;
+        world.add_component(second, Name("second"))?;
+
+        start_run(&mut world);
+        assert_eq!(world.query_filtered::<Entity, Added<Name>>().count(), 0);
+        assert_eq!(world.query_filtered::<Entity, Changed<Name>>().count(), 0);
+
+        for (entity, mut name) in world.query::<(Entity, &mut Name)>() {
+            if entity == second {
+                name.0 = "changed";
+            }
+        }
+        let third = world.spawn();
+        world.add_component(third, Name("third"))?;
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Changed<Name>>()
+                .collect::<Vec<_>>(),
+            [second, third]
+        );
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Added<Name>>()
+                .collect::<Vec<_>>(),
+            [third]
+        );
+
+        // Ticks move along with values swapped in by removal
+        start_run(&mut world);
+        world.remove_component::<Name>(first);
+        assert_eq!(world.query_filtered::<Entity, Changed<Name>>().count(), 0);
+        world.get_component_mut::<Name>(third);
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Changed<Name>>()
+                .collect::<Vec<_>>(),
+            [third]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn component_hooks() -> Result<()> {
+        #[derive(Default)]
+        struct Log(Vec<String>);
+
+        fn log(world: &mut World, message: String) {
+            if let Some(log) = world.resource_mut::<Log>() {
+                log.0.push(message);
+            }
+        }
+
+        let mut world = World::new();
+        world.insert_resource(Log::default());
+        world
+            .component_hooks_mut::<Name>()
+            .on_add(|world, entity| {
+                let name = world.get_component::<Name>(entity).unwrap().0;
+                log(world, format!("add {name}"));
+            })
+            .on_replace(|world, entity| {
+                let name = world.get_component::<Name>(entity).unwrap().0;
+                log(world, format!("replace {name}"));
+            })
+            .on_remove(|world, entity| {
+                let name = world.get_component::<Name>(entity).unwrap().0;
+                log(world, format!("remove {name}"));
+            });
+
+        let first = world.spawn();
+        let second = world.spawn();
+        world.add_component(first, Name("first"))?;
+        world.add_component(first, Name("renamed"))?;
+        world.add_component(second, Name("second"))?;
+        world.add_component(second, Position(0.0, 0.0))?;
+        world.remove_component::<Name>(first);
+        world.remove_component::<Name>(first);
+        world.despawn(second);
+
+        assert_eq!(
+            world.resource::<Log>().unwrap().0,
+            [
+                "add first",
+                "replace first",
+                "add second",
+                "remove renamed",
+                "remove second"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn replace_hooks_can_despawn_or_remove() -> Result<()> {
+        let mut world = World::new();
+        world.insert_resource(0_u32);
+        world
+            .component_hooks_mut::<Name>()
+            .on_add(|world, _| {
+                if let Some(added) = world.resource_mut::<u32>() {
+                    *added += 1;
+                }
+            })
+            .on_replace(|world, entity| match world.get_component::<Name>(entity) {
+                Some(Name("despawn")) => world.despawn(entity),
+                Some(Name("remove")) => world.remove_component::<Name>(entity),
+                _ => {}
+            });
+
+        // The entity is gone once the hook returns, so nothing is added
+        let despawned = world.spawn();
+        world.add_component(despawned, Name("despawn"))?;
+        assert!(world.add_component(despawned, Name("new")).is_err());
+        assert!(world.entities().is_empty());
+        assert_eq!(world.query::<&Name>().count(), 0);
+
+        // With the old component removed, the new one counts as added again
+        let removed = world.spawn();
+        world.add_component(removed, Name("remove"))?;
+        world.add_component(removed, Name("new"))?;
+        assert_eq!(world.get_component(removed), Some(&Name("new")));
+        assert_eq!(world.resource(), Some(&3_u32));
+        Ok(())
+    }
+
+    #[test]
+    fn components_added_during_despawn_are_removed() -> Result<()> {
+        let mut world = World::new();
+        world.insert_resource(Vec::<&'static str>::new());
+        world
+            .component_hooks_mut::<Name>()
+            .on_remove(|world, entity| {
+                let _ = world.add_component(entity, Position(0.0, 0.0));
+            });
+        world
+            .component_hooks_mut::<Position>()
+            .on_remove(|world, _| {
+                if let Some(log) = world.resource_mut::<Vec<&'static str>>() {
+                    log.push("remove position");
+                }
+            });
+
+        let entity = world.spawn();
+        world.add_component(entity, Name("name"))?;
+        world.despawn(entity);
+        assert!(world.entities().is_empty());
+        assert_eq!(world.query::<&Position>().count(), 0);
+        assert_eq!(
+            world.resource::<Vec<&'static str>>(),
+            Some(&vec!["remove position"])
+        );
+        assert_eq!(
+            world.removed_components::<Position>().collect::<Vec<_>>(),
+            [entity]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn removed_components() -> Result<()> {
+        let mut world = World::new();
+        let entities = (0..3)
+            .map(|index| {
+                let entity = world.spawn();
+                world.add_component(entity, Position(index as f32, 0.0))?;
+                Ok(entity)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        world.remove_component::<Position>(entities[1]);
+        world.despawn(entities[2]);
+        world.despawn(entities[1]);
+        assert_eq!(
+            world.removed_components::<Position>().collect::<Vec<_>>(),
+            [entities[1], entities[2]]
+        );
+        assert_eq!(world.removed_components::<Velocity>().count(), 0);
+
+        // Removals before the last run are no longer reported
+        start_run(&mut world);
+        world.remove_component::<Position>(entities[0]);
+        assert_eq!(
+            world.removed_components::<Position>().collect::<Vec<_>>(),
+            [entities[0]]
+        );
+
+        world.set_last_run_tick(0);
+        world.clear_removed_components(world.change_tick());
+        assert_eq!(
+            world.removed_components::<Position>().collect::<Vec<_>>(),
+            [entities[0]]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn old_ticks_are_clamped_before_they_wrap_around() -> Result<()> {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.add_component(entity, 0_u32)?;
+
+        // Without clamping, tick 0 would look 51 ticks old once the tick wraps around
+        world.change_tick = 3_500_000_000;
+        world.increment_change_tick();
+        world.change_tick = 50;
+        let this_run = world.increment_change_tick();
+        world.set_last_run_tick(this_run.wrapping_sub(100));
+
+        assert_eq!(world.query_filtered::<Entity, Changed<u32>>().count(), 0);
+        assert_eq!(world.query_filtered::<Entity, Added<u32>>().count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn removals_are_dropped_after_two_updates() -> Result<()> {
+        let mut world = World::new();
+        let [first, second] = [(); 2].map(|_| world.spawn());
+        world.add_component(first, Position(0.0, 0.0))?;
+        world.add_component(second, Position(0.0, 0.0))?;
+
+        world.remove_component::<Position>(first);
+        world.increment_change_tick();
+        world.update_removed_components();
+        world.remove_component::<Position>(second);
+        world.increment_change_tick();
+        assert_eq!(world.removed_components::<Position>().count(), 2);
+
+        world.update_removed_components();
+        assert_eq!(
+            world.removed_components::<Position>().collect::<Vec<_>>(),
+            [second]
+        );
+        world.increment_change_tick();
+        world.update_removed_components();
+        assert_eq!(world.removed_components::<Position>().count(), 0);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    mod prefabs {
+        use super::*;
+        use crate::{
+            hierarchy::{Children, Parent, Transform},
+            scene::{MapEntities, SceneRegistry},
+        };
+        use serde::{Deserialize, Serialize};
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Health(u32);
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Wielding(Entity);
+
+        impl MapEntities for Wielding {
+            fn map_entities(&mut self, entity_map: &crate::scene::EntityMap) {
+                self.0 = entity_map.map(self.0);
+            }
+        }
+
+        fn registry() -> SceneRegistry {
+            let mut registry = SceneRegistry::new();
+            registry
+                .register::<Health>("Health")
+                .register_mapped::<Wielding>("Wielding");
+            registry
+        }
+
+        /// An enemy wielding a sword that is attached to it
+        fn enemy(registry: &SceneRegistry) -> Result<Prefab> {
+            let mut world = World::new();
+            let enemy = world.spawn();
+            let sword = world.spawn();
+            world.add_component(enemy, Health(10))?;
+            world.add_component(enemy, Wielding(sword))?;
+            world.add_component(sword, Transform::default())?;
+            world.set_parent(sword, enemy)?;
+            Prefab::from_entity(&world, registry, enemy)
+        }
+
+        fn wielded(world: &World, entity: Entity) -> Entity {
+            world.get_component::<Wielding>(entity).unwrap().0
+        }
+
+        #[test]
+        fn instances_reference_their_own_entities() -> Result<()> {
+            let registry = registry();
+            let prefab = Prefab::from_json(&enemy(&registry)?.to_json()?)?;
+
+            let mut world = World::new();
+            let first = prefab.instantiate(&mut world, &registry)?;
+            let second = prefab.instantiate(&mut world, &registry)?;
+            assert_eq!(world.entities().len(), 4);
+            assert_ne!(first.root, second.root);
+
+            for instance in [&first, &second] {
+                let sword = wielded(&world, instance.root);
+                assert_eq!(world.get_component(sword), Some(&Parent(instance.root)));
+                assert_eq!(
+                    world.get_component(instance.root),
+                    Some(&Children(vec![sword]))
+                );
+                assert_eq!(
+                    instance.entity_map.get(prefab.entities[1].entity),
+                    Some(sword)
+                );
+            }
+            assert_ne!(wielded(&world, first.root), wielded(&world, second.root));
+            Ok(())
+        }
+
+        #[test]
+        fn overrides_replace_components_per_instance() -> Result<()> {
+            let registry = registry();
+            let prefab = enemy(&registry)?;
+            let root = prefab.root().unwrap();
+
+            let mut world = World::new();
+            let mut overrides = PrefabOverrides::new();
+            overrides.set(root, "Health", Health(50))?;
+            let boss = prefab.instantiate_with(&mut world, &registry, &overrides)?;
+            let grunt = prefab.instantiate(&mut world, &registry)?;
+
+            assert_eq!(world.get_component(boss.root), Some(&Health(50)));
+            assert_eq!(world.get_component(grunt.root), Some(&Health(10)));
+            Ok(())
+        }
+
+        #[test]
+        fn nested_prefabs() -> Result<()> {
+            let registry = registry();
+            let enemy = enemy(&registry)?;
+
+            // A squad leader with two enemies, the second of which is tougher
+            let [leader, first, second] = [0, 1, 2].map(Handle::from_bits);
+            let member =
+                |entity: Entity, components: BTreeMap<String, serde_json::Value>| PrefabEntity {
+                    entity,
+                    prefab: Some(Box::new(enemy.clone())),
+                    components,
+                };
+            let parent = serde_json::to_value(Parent(leader))?;
+            let squad = Prefab {
+                entities: vec![
+                    PrefabEntity {
+                        entity: leader,
+                        prefab: None,
+                        components: BTreeMap::from([(
+                            "Children".to_string(),
+                            serde_json::to_value(Children(vec![first, second]))?,
+                        )]),
+                    },
+                    member(
+                        first,
+                        BTreeMap::from([("Parent".to_string(), parent.clone())]),
+                    ),
+                    member(
+                        second,
+                        BTreeMap::from([
+                            ("Parent".to_string(), parent),
+                            ("Health".to_string(), serde_json::to_value(Health(30))?),
+                        ]),
+                    ),
+                ],
+            };
+            let squad = Prefab::from_ron(&squad.to_ron()?)?;
+
+            let mut world = World::new();
+            let instance = squad.instantiate(&mut world, &registry)?;
+            assert_eq!(world.entities().len(), 5);
+
+            let members = world
+                .get_component::<Children>(instance.root)
+                .unwrap()
+                .0
+                .clone();
+            assert_eq!(members.len(), 2);
+            for (member, health) in members.iter().zip([10, 30]) {
+                assert_eq!(world.get_component(*member), Some(&Parent(instance.root)));
+                assert_eq!(world.get_component(*member), Some(&Health(health)));
+
+                // References inside each nested prefab stay within that instance
+                let sword = wielded(&world, *member);
+                assert_eq!(world.get_component(sword), Some(&Parent(*member)));
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn subtrees_are_captured_without_their_parent() -> Result<()> {
+            let registry = registry();
+            let mut world = World::new();
+            let army = world.spawn();
+            let enemy = world.spawn();
+            let sword = world.spawn();
+            world.add_component(enemy, Health(10))?;
+            world.add_component(enemy, Wielding(sword))?;
+            world.set_parent(enemy, army)?;
+            world.set_parent(sword, enemy)?;
+
+            let prefab = Prefab::from_entity(&world, &registry, enemy)?;
+            assert_eq!(prefab.entities.len(), 2);
+            assert!(!prefab.entities[0].components.contains_key("Parent"));
+
+            let instance = prefab.instantiate(&mut world, &registry)?;
+            let sword = wielded(&world, instance.root);
+            assert_eq!(world.get_component::<Parent>(instance.root), None);
+            assert_eq!(world.get_component(sword), Some(&Parent(instance.root)));
+            assert_eq!(world.get_component(army), Some(&Children(vec![enemy])));
+            Ok(())
+        }
+
+        #[test]
+        fn empty_prefabs_fail_to_instantiate() {
+            let mut world = World::new();
+            assert!(Prefab::default()
+                .instantiate(&mut world, &registry())
+                .is_err());
+        }
+
+        #[test]
+        fn failed_instances_leave_nothing_behind() -> Result<()> {
+            let mut world = World::new();
+            let enemy = world.spawn();
+            let sword = world.spawn();
+            world.add_component(enemy, Health(10))?;
+            world.add_component(enemy, Wielding(sword))?;
+            world.set_parent(sword, enemy)?;
+            let prefab = Prefab::from_entity(&world, &registry(), enemy)?;
+
+            // `Wielding` is not registered here, so loading it fails
+            let mut world = World::new();
+            assert!(prefab
+                .instantiate(&mut world, &SceneRegistry::default())
+                .is_err());
+            assert!(world.entities().is_empty());
+            Ok(())
+        }
+    }
+}